@@ -1,63 +1,35 @@
-use itertools::Itertools;
+use std::collections::VecDeque;
 
-type Memory<'a> = &'a mut [i32];
-const STEP_SIZE: usize = 4;
+use intcode::solver::Search;
+use intcode::{loader, Computer};
 
-struct Computer<'a> {
-    pc: usize,
-    memory: Memory<'a>,
-}
-
-impl<'a> Computer<'a> {
-    fn step(&mut self) {
-        //TODO: clean up this implementation.
-        match self.memory[self.pc] {
-            1 => self.memory[self.memory[self.pc + 3] as usize] = self.memory[self.memory[self.pc + 1] as usize] + self.memory[self.memory[self.pc + 2] as usize],
-            2 => self.memory[self.memory[self.pc + 3] as usize] = self.memory[self.memory[self.pc + 1] as usize] * self.memory[self.memory[self.pc + 2] as usize],
-            99 => return,
-            _ => unreachable!(),
-        }
-        self.pc += STEP_SIZE;
-    }
-
-    fn finished(&self) -> bool {
-        self.memory[self.pc] == 99
-    }
-    
-    fn result(&self) -> i32 {
-        self.memory[0]
-    }
+fn run(program: &[i32], noun: i32, verb: i32) -> i32 {
+    let mut memory = program.to_vec();
+    memory[1] = noun;
+    memory[2] = verb;
+    let mut computer = Computer::from_program(&memory);
+    computer
+        .run(&mut VecDeque::new(), &mut Vec::new())
+        .expect("Intcode program failed")
 }
 
 fn main() {
-    let input = std::fs::read_to_string("input.txt").expect("Input file not found.");
-    let parsed_input = input.split(",").filter_map(|s| s.parse::<i32>().ok()).collect::<Vec<_>>();
-    let mut memory1 = parsed_input.clone(); 
+    let parsed_input = loader::load::<i32, _>("input.txt").expect("Invalid input file");
     //Fix up input for part1
-    memory1[1] = 12;
-    memory1[2] = 2;
-    let mut computer = Computer{ pc: 0, memory: &mut memory1};
-    while !computer.finished() {
-        computer.step();
-    }
-    println!("{}", computer.result());
-
-    let mut result2 = 0;
-    for (noun, verb) in (0..=99).tuple_combinations() {
-        let mut memory = parsed_input.clone();
-        memory[1] = noun;
-        memory[2] = verb;
+    println!("{}", run(&parsed_input, 12, 2));
 
-        let mut computer = Computer{ pc: 0, memory: &mut memory};
-        while !computer.finished() {
-            computer.step();
-        }
-        if computer.result() == 19690720 {
-            result2 = 100 * noun + verb;
-            break;
-        }
+    let mut search = Search::new(&parsed_input, 19690720);
+    search.add_cell(1, 0..=99);
+    search.add_cell(2, 0..=99);
+    //The exhaustive search covers programs the symbolic one can not follow
+    let solution = match search.symbolic() {
+        Ok(solution) => solution,
+        Err(_) => search.exhaustive().expect("Search failed"),
+    };
+    match solution {
+        Some(values) => println!("{}", 100 * values[0] + values[1]),
+        None => println!("No noun and verb produce 19690720"),
     }
-    println!("{}", result2);
 }
 
 
@@ -67,42 +39,38 @@ mod test {
 
     #[test]
     fn test_simple() {
-        let mut memory = vec![1, 0, 0, 0, 99];
-        let mut computer = Computer{ pc: 0, memory: &mut memory };
-        computer.step();
+        let mut computer = Computer::new(&[1, 0, 0, 0, 99]);
+        computer.step().unwrap();
         assert!(computer.finished());
-        computer.step();
+        computer.step().unwrap();
         assert!(computer.finished());
         assert_eq!(computer.result(), 2);
     }
 
     #[test]
     fn test2() {
-        let mut memory = vec![2, 3, 0, 3, 99];
-        let mut computer = Computer{ pc: 0, memory: &mut memory };
-        computer.step();
+        let mut computer = Computer::new(&[2, 3, 0, 3, 99]);
+        computer.step().unwrap();
         assert!(computer.finished());
-        computer.step();
+        computer.step().unwrap();
         assert!(computer.finished());
         assert_eq!(computer.result(), 2);
     }
 
     #[test]
     fn test3() {
-        let mut memory = vec![2, 4, 4, 5, 99, 0];
-        let mut computer = Computer{ pc: 0, memory: &mut memory };
+        let mut computer = Computer::new(&[2, 4, 4, 5, 99, 0]);
         while !computer.finished() {
-            computer.step();
+            computer.step().unwrap();
         }
         assert_eq!(computer.result(), 2);
     }
 
     #[test]
     fn test4() {
-        let mut memory = vec![1, 1, 1, 4, 99, 5, 6, 0, 99];
-        let mut computer = Computer{ pc: 0, memory: &mut memory };
+        let mut computer = Computer::new(&[1, 1, 1, 4, 99, 5, 6, 0, 99]);
         while !computer.finished() {
-            computer.step();
+            computer.step().unwrap();
         }
         assert_eq!(computer.result(), 30);
     }
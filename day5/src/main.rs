@@ -1,14 +1,14 @@
-mod computer;
-
-use crate::computer::Computer;
-
-fn parse_input(input: &str) -> Vec<i64> {
-    input.split(',').map(|s| s.parse::<i64>().unwrap()).collect()
-}
+use intcode::device::{Stdin, Stdout};
+use intcode::{loader, Computer};
 
 fn main() {
-    let input = std::fs::read_to_string("input.txt").expect("Input file not found.");
-    let mut memory = parse_input(&input);
-    let mut computer = Computer::new(&mut memory);
-    computer.run();
+    let memory = loader::load("input.txt").unwrap_or_else(|err| {
+        eprintln!("input.txt: {}", err);
+        std::process::exit(1);
+    });
+    let mut computer = Computer::new(&memory);
+    if let Err(err) = computer.run(&mut Stdin, &mut Stdout) {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,152 @@
+use crate::computer::{Computer, State};
+use crate::error::{ErrorKind, IntcodeError};
+use crate::word::Word;
+
+//Copies of one program wired in series, every output is an input of the next
+//machine. With feedback the outputs of the last machine go back to the first
+//until the last machine halts.
+pub struct Chain<W = i64> {
+    machines: Vec<Computer<W>>,
+    halted: Vec<bool>,
+    feedback: bool,
+}
+
+impl<W: Word> Chain<W> {
+    //One machine per phase, each gets its phase as first input.
+    pub fn new(program: &[W], phases: &[W]) -> Self {
+        let machines = phases
+            .iter()
+            .map(|&phase| {
+                let mut machine = Computer::from_program(program);
+                machine.push_input(phase);
+                machine
+            })
+            .collect::<Vec<_>>();
+        Chain {
+            halted: vec![false; machines.len()],
+            machines,
+            feedback: false,
+        }
+    }
+
+    pub fn set_feedback(&mut self, feedback: bool) {
+        self.feedback = feedback;
+    }
+
+    pub fn machines(&self) -> &[Computer<W>] {
+        &self.machines
+    }
+
+    //Feeds signal into the first machine and returns the last value the last
+    //machine produced, None if it produced nothing. Without feedback this is a
+    //single pass, so machines still waiting for input can get more signals by
+    //calling run again.
+    pub fn run(&mut self, signal: W) -> Result<Option<W>, IntcodeError> {
+        let mut signals = vec![signal];
+        let mut last = None;
+        loop {
+            for (machine, halted) in self.machines.iter_mut().zip(self.halted.iter_mut()) {
+                for value in signals.drain(..) {
+                    machine.push_input(value);
+                }
+                while !*halted {
+                    match machine.resume()? {
+                        State::Output(value) => signals.push(value),
+                        State::Halted => *halted = true,
+                        State::NeedsInput => break,
+                    }
+                }
+            }
+            last = signals.last().copied().or(last);
+            if !self.feedback || self.halted.last().copied().unwrap_or(true) {
+                return Ok(last);
+            }
+            if signals.is_empty() {
+                //Every machine waits for input that never comes
+                let waiting = self.halted.iter().position(|halted| !halted).unwrap();
+                return Err(self.machines[waiting].fault(ErrorKind::NoInput));
+            }
+        }
+    }
+}
+
+fn permutations<W: Copy>(values: &mut Vec<W>, start: usize, visit: &mut dyn FnMut(&[W])) {
+    if start == values.len() {
+        visit(values);
+        return;
+    }
+    for i in start..values.len() {
+        values.swap(start, i);
+        permutations(values, start + 1, visit);
+        values.swap(start, i);
+    }
+}
+
+//Tries every ordering of phases and returns the one with the highest final
+//signal together with that signal.
+pub fn best_phases<W: Word>(
+    program: &[W],
+    phases: &[W],
+    signal: W,
+    feedback: bool,
+) -> Result<Option<(Vec<W>, W)>, IntcodeError> {
+    let mut best: Option<(Vec<W>, W)> = None;
+    let mut error = None;
+    permutations(&mut phases.to_vec(), 0, &mut |order| {
+        if error.is_some() {
+            return;
+        }
+        let mut chain = Chain::new(program, order);
+        chain.set_feedback(feedback);
+        match chain.run(signal) {
+            Ok(Some(value)) if best.as_ref().is_none_or(|best| value > best.1) => {
+                best = Some((order.to_vec(), value));
+            }
+            Ok(_) => {}
+            Err(err) => error = Some(err),
+        }
+    });
+    match error {
+        Some(err) => Err(err),
+        None => Ok(best),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const SERIES: [i64; 17] = [
+        3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+    ];
+    const FEEDBACK: [i64; 29] = [
+        3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1, 28,
+        1005, 28, 6, 99, 0, 0, 5,
+    ];
+
+    #[test]
+    fn series() {
+        let mut chain = Chain::new(&SERIES, &[4, 3, 2, 1, 0]);
+        assert_eq!(chain.run(0).unwrap(), Some(43210));
+        let best = best_phases(&SERIES, &[0, 1, 2, 3, 4], 0, false).unwrap();
+        assert_eq!(best, Some((vec![4, 3, 2, 1, 0], 43210)));
+    }
+
+    #[test]
+    fn feedback_loop() {
+        let mut chain = Chain::new(&FEEDBACK, &[9, 8, 7, 6, 5]);
+        chain.set_feedback(true);
+        assert_eq!(chain.run(0).unwrap(), Some(139629729));
+        let best = best_phases(&FEEDBACK, &[5, 6, 7, 8, 9], 0, true).unwrap();
+        assert_eq!(best, Some((vec![9, 8, 7, 6, 5], 139629729)));
+    }
+
+    #[test]
+    fn starved_chain_faults() {
+        //Waits for a third input, but never outputs anything to feed back
+        let mut chain = Chain::new(&[3, 0, 3, 0, 3, 0, 99], &[1]);
+        chain.set_feedback(true);
+        let err = chain.run(2).unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::NoInput));
+    }
+}
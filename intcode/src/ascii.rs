@@ -0,0 +1,175 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::computer::{Computer, State};
+use crate::error::IntcodeError;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Text {
+    //A printed line without its newline. Text printed right before the program
+    //waits for input or halts comes as a line too, that is usually a prompt.
+    Line(String),
+    //Output that is no ASCII character, e.g. the answer of a puzzle
+    Value(i64),
+}
+
+//A character of a line that has no ASCII code. Column counts characters from 1.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct NotAscii {
+    pub character: char,
+    pub column: usize,
+}
+
+impl fmt::Display for NotAscii {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} at column {} is no ASCII character",
+            self.character, self.column
+        )
+    }
+}
+
+impl Error for NotAscii {}
+
+//Drives a program that talks ASCII: input is sent as lines, output is
+//collected into lines.
+pub struct Ascii {
+    computer: Computer,
+    line: String,
+    halted: bool,
+}
+
+impl Ascii {
+    pub fn new(computer: Computer) -> Self {
+        Ascii {
+            computer,
+            line: String::new(),
+            halted: false,
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn halted(&self) -> bool {
+        self.halted
+    }
+
+    //Queues the ASCII codes of line followed by a newline. Nothing is queued
+    //if the line has other characters.
+    pub fn send_line(&mut self, line: &str) -> Result<(), NotAscii> {
+        if let Some((index, character)) = line.chars().enumerate().find(|(_, c)| !c.is_ascii()) {
+            return Err(NotAscii {
+                character,
+                column: index + 1,
+            });
+        }
+        for c in line.chars().chain(Some('\n')) {
+            self.computer.push_input(c as i64);
+        }
+        Ok(())
+    }
+
+    //Runs until the program halts or waits for input and returns everything
+    //it printed meanwhile. The text is returned on faults as well, the last
+    //message before a crash is often the one that explains it.
+    pub fn resume(&mut self) -> (Vec<Text>, Option<IntcodeError>) {
+        let mut text = Vec::new();
+        let mut fault = None;
+        loop {
+            let state = match self.computer.resume() {
+                Ok(state) => state,
+                Err(err) => {
+                    fault = Some(err);
+                    break;
+                }
+            };
+            match state {
+                State::Output(10) => text.push(Text::Line(std::mem::take(&mut self.line))),
+                State::Output(value @ 0..=127) => self.line.push(value as u8 as char),
+                State::Output(value) => {
+                    self.flush(&mut text);
+                    text.push(Text::Value(value));
+                }
+                State::NeedsInput => break,
+                State::Halted => {
+                    self.halted = true;
+                    break;
+                }
+            }
+        }
+        self.flush(&mut text);
+        (text, fault)
+    }
+
+    fn flush(&mut self, text: &mut Vec<Text>) {
+        if !self.line.is_empty() {
+            text.push(Text::Line(std::mem::take(&mut self.line)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    //Prints a prompt, echoes one line back upper-cased by subtracting 32 from
+    //letters and finishes with a number
+    const SHOUT: &str = "
+                OUT #62
+        loop:   IN [c]
+                EQ [c], #10, [tmp]
+                JT [tmp], #done
+                LT [c], #97, [tmp]
+                JT [tmp], #print
+                ADD [c], #-32, [c]
+        print:  OUT [c]
+                JT #1, #loop
+        done:   OUT #10
+                OUT #1000
+                HALT
+        c:      data 0
+        tmp:    data 0
+    ";
+
+    #[test]
+    fn lines_and_values() {
+        let mut ascii = Ascii::new(Computer::new(&assemble(SHOUT).unwrap()));
+        let (text, fault) = ascii.resume();
+        assert_eq!(text, vec![Text::Line(">".to_string())]);
+        assert!(fault.is_none());
+        assert!(!ascii.halted());
+        assert_eq!(
+            ascii.send_line("hé"),
+            Err(NotAscii {
+                character: 'é',
+                column: 2
+            })
+        );
+        ascii.send_line("hi there").unwrap();
+        assert_eq!(
+            ascii.resume().0,
+            vec![Text::Line("HI THERE".to_string()), Text::Value(1000)]
+        );
+        assert!(ascii.halted());
+    }
+
+    #[test]
+    fn keeps_text_of_faults() {
+        let mut ascii = Ascii::new(Computer::new(&[104, 104, 104, 105, 104, 10, 104, 33, 42]));
+        let (text, fault) = ascii.resume();
+        assert_eq!(
+            text,
+            vec![Text::Line("hi".to_string()), Text::Line("!".to_string())]
+        );
+        assert!(fault.is_some());
+        assert!(!ascii.halted());
+    }
+}
@@ -0,0 +1,726 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use crate::computer::{opcode_info, split_instruction, Mode};
+
+const OPCODES: [i64; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+const MAX_MACRO_DEPTH: usize = 16;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for AsmError {}
+
+enum Term {
+    Number(i64),
+    Label(String),
+}
+
+//Sum of signed terms, e.g. `buffer+2` or `-7`.
+struct Expr {
+    terms: Vec<(i64, Term)>,
+    column: usize,
+}
+
+enum Body {
+    Data(Vec<Expr>),
+    Instruction {
+        word: i64,
+        operands: Vec<(Mode, Expr)>,
+    },
+    Invoke {
+        name: String,
+        args: Vec<String>,
+    },
+}
+
+//Something in front of a statement: `name:` or an address check like `0012:`.
+enum Mark {
+    Label(String),
+    Address(usize),
+}
+
+struct Statement {
+    labels: Vec<(Mark, usize)>,
+    body: Option<(Body, usize)>,
+}
+
+struct Macro {
+    params: Vec<String>,
+    body: Vec<(usize, String)>,
+}
+
+enum Pending {
+    Word(i64),
+    Expr(Expr, usize),
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+}
+
+fn strip_comment(text: &str) -> &str {
+    text.split(';').next().unwrap_or("")
+}
+
+fn is_ident_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+pub(crate) fn find_opcode(mnemonic: &str) -> Option<i64> {
+    OPCODES.iter().copied().find(|&opcode| {
+        opcode_info(opcode).is_some_and(|(name, _, _)| name.eq_ignore_ascii_case(mnemonic))
+    })
+}
+
+impl Parser {
+    fn new(text: &str, line: usize) -> Self {
+        Parser {
+            chars: strip_comment(text).chars().collect(),
+            pos: 0,
+            line,
+        }
+    }
+
+    fn error<T>(&self, column: usize, message: String) -> Result<T, AsmError> {
+        Err(AsmError {
+            line: self.line,
+            column,
+            message,
+        })
+    }
+
+    fn column(&self) -> usize {
+        self.pos + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    fn at_end(&mut self) -> bool {
+        self.skip_ws();
+        self.pos == self.chars.len()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_ws();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), AsmError> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            self.error(self.column(), format!("expected `{}`", c))
+        }
+    }
+
+    fn ident(&mut self) -> Option<String> {
+        self.skip_ws();
+        if !self.peek().is_some_and(is_ident_start) {
+            return None;
+        }
+        let start = self.pos;
+        while self.peek().is_some_and(is_ident_char) {
+            self.pos += 1;
+        }
+        Some(self.chars[start..self.pos].iter().collect())
+    }
+
+    //The sign is applied before the range check, so i64::MIN can be written.
+    fn number(&mut self, sign: i64) -> Result<Option<i64>, AsmError> {
+        self.skip_ws();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Ok(None);
+        }
+        let digits = self.chars[start..self.pos].iter().collect::<String>();
+        let value = digits
+            .parse::<i128>()
+            .ok()
+            .and_then(|value| i64::try_from(sign as i128 * value).ok());
+        match value {
+            Some(value) => Ok(Some(value)),
+            None if sign < 0 => self.error(start + 1, format!("number -{} is too small", digits)),
+            None => self.error(start + 1, format!("number {} is too large", digits)),
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, AsmError> {
+        self.skip_ws();
+        let column = self.column();
+        let mut terms = Vec::new();
+        let mut sign = if self.eat('-') { -1 } else { 1 };
+        loop {
+            self.skip_ws();
+            let term = if let Some(value) = self.number(sign)? {
+                (1, Term::Number(value))
+            } else if let Some(name) = self.ident() {
+                (sign, Term::Label(name))
+            } else {
+                return self.error(self.column(), "expected a number or label".to_string());
+            };
+            terms.push(term);
+            sign = if self.eat('+') {
+                1
+            } else if self.eat('-') {
+                -1
+            } else {
+                return Ok(Expr { terms, column });
+            };
+        }
+    }
+
+    fn operand(&mut self) -> Result<(Mode, Expr), AsmError> {
+        self.skip_ws();
+        let start = self.pos;
+        if self.eat('[') {
+            let expr = self.expr()?;
+            self.expect(']')?;
+            return Ok((Mode::Position, expr));
+        }
+        if self.eat('#') {
+            return Ok((Mode::Immediate, self.expr()?));
+        }
+        if self.ident().as_deref() == Some("rb") {
+            self.skip_ws();
+            let column = self.column();
+            if self.eat('+') {
+                return Ok((Mode::Relative, self.expr()?));
+            }
+            if self.peek() == Some('-') {
+                return Ok((Mode::Relative, self.expr()?));
+            }
+            let zero = Expr {
+                terms: vec![(1, Term::Number(0))],
+                column,
+            };
+            return Ok((Mode::Relative, zero));
+        }
+        self.error(
+            start + 1,
+            "expected an operand like `[x]`, `#x` or `rb+x`".to_string(),
+        )
+    }
+
+    fn list<T>(
+        &mut self,
+        mut item: impl FnMut(&mut Self) -> Result<T, AsmError>,
+    ) -> Result<Vec<T>, AsmError> {
+        let mut items = Vec::new();
+        if self.at_end() {
+            return Ok(items);
+        }
+        loop {
+            items.push(item(self)?);
+            if self.at_end() {
+                return Ok(items);
+            }
+            self.expect(',')?;
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, AsmError> {
+        let mut labels = Vec::new();
+        loop {
+            self.skip_ws();
+            let start = self.pos;
+            if let Some(address) = self.number(1)? {
+                self.expect(':')?;
+                labels.push((Mark::Address(address as usize), start + 1));
+                continue;
+            }
+            if let Some(name) = self.ident() {
+                if self.eat(':') {
+                    labels.push((Mark::Label(name), start + 1));
+                    continue;
+                }
+                self.pos = start;
+            }
+            break;
+        }
+        if self.at_end() {
+            return Ok(Statement { labels, body: None });
+        }
+
+        let column = self.column();
+        let name = match self.ident() {
+            Some(name) => name,
+            None => return self.error(column, "expected a mnemonic".to_string()),
+        };
+        let body = if name.eq_ignore_ascii_case("data") {
+            let values = self.list(Parser::expr)?;
+            if values.is_empty() {
+                return self.error(column, "data needs at least one value".to_string());
+            }
+            Body::Data(values)
+        } else if let Some(opcode) = find_opcode(&name) {
+            let (_, count, target) = opcode_info(opcode).unwrap();
+            //An explicit instruction word like `OUT(1104)` keeps redundant mode digits
+            let explicit = if self.eat('(') {
+                let column = self.column();
+                let word = match self.number(1)? {
+                    Some(word) => word,
+                    None => return self.error(column, "expected an instruction word".to_string()),
+                };
+                self.expect(')')?;
+                Some((word, column))
+            } else {
+                None
+            };
+            let operands = self.list(Parser::operand)?;
+            if operands.len() != count {
+                return self.error(
+                    column,
+                    format!(
+                        "{} takes {} operands, found {}",
+                        name,
+                        count,
+                        operands.len()
+                    ),
+                );
+            }
+            if let Some(target) = target {
+                if operands[target].0 == Mode::Immediate {
+                    return self.error(
+                        operands[target].1.column,
+                        "an immediate operand can not be written".to_string(),
+                    );
+                }
+            }
+            let mut word = opcode;
+            let mut scale = 100;
+            for (mode, _) in &operands {
+                word += scale * *mode as i64;
+                scale *= 10;
+            }
+            if let Some((explicit, column)) = explicit {
+                let fits = split_instruction(explicit).is_ok_and(|(code, mode1, mode2, mode3)| {
+                    code == opcode
+                        && [mode1, mode2, mode3]
+                            .iter()
+                            .zip(&operands)
+                            .all(|(mode, operand)| *mode == operand.0)
+                });
+                if !fits {
+                    return self.error(
+                        column,
+                        format!("instruction word {} does not fit {}", explicit, name),
+                    );
+                }
+                word = explicit;
+            }
+            Body::Instruction { word, operands }
+        } else {
+            let args = strip_comment(&self.chars[self.pos..].iter().collect::<String>())
+                .split(',')
+                .map(|arg| arg.trim().to_string())
+                .filter(|arg| !arg.is_empty())
+                .collect();
+            self.pos = self.chars.len();
+            Body::Invoke { name, args }
+        };
+        Ok(Statement {
+            labels,
+            body: Some((body, column)),
+        })
+    }
+}
+
+//Replaces whole identifiers that name a parameter and `@@` with a suffix unique
+//to this expansion, so macros can define their own labels.
+fn substitute(text: &str, params: &HashMap<&str, &str>, unique: &str) -> String {
+    let text = text.replace("@@", unique);
+    let mut result = String::new();
+    let mut ident = String::new();
+    for c in text.chars().chain(std::iter::once('\n')) {
+        if is_ident_char(c) && (!ident.is_empty() || is_ident_start(c)) {
+            ident.push(c);
+            continue;
+        }
+        if !ident.is_empty() {
+            result.push_str(params.get(ident.as_str()).copied().unwrap_or(&ident));
+            ident.clear();
+        }
+        result.push(c);
+    }
+    result.pop();
+    result
+}
+
+#[derive(Default)]
+struct Assembler {
+    macros: HashMap<String, Macro>,
+    labels: HashMap<String, usize>,
+    words: Vec<Pending>,
+    expansions: usize,
+    //Position of the outermost macro invocation while expanding one
+    site: Option<(usize, usize)>,
+}
+
+impl Assembler {
+    fn position(&self, line: usize, column: usize) -> (usize, usize) {
+        self.site.unwrap_or((line, column))
+    }
+
+    fn line(&mut self, text: &str, line: usize, depth: usize) -> Result<(), AsmError> {
+        let mut parser = Parser::new(text, line);
+        let statement = parser.statement()?;
+        for (mark, column) in statement.labels {
+            match mark {
+                Mark::Label(name) => {
+                    if name == "rb" {
+                        return parser.error(column, "`rb` can not be used as label".to_string());
+                    }
+                    if self.labels.insert(name.clone(), self.words.len()).is_some() {
+                        return parser.error(column, format!("duplicate label `{}`", name));
+                    }
+                }
+                Mark::Address(address) if address != self.words.len() => {
+                    return parser.error(
+                        column,
+                        format!(
+                            "address {} does not match location {}",
+                            address,
+                            self.words.len()
+                        ),
+                    );
+                }
+                Mark::Address(_) => {}
+            }
+        }
+
+        let (body, column) = match statement.body {
+            Some(body) => body,
+            None => return Ok(()),
+        };
+        match body {
+            Body::Data(values) => {
+                for value in values {
+                    self.expr(value, line);
+                }
+            }
+            Body::Instruction { word, operands } => {
+                self.words.push(Pending::Word(word));
+                for (_, value) in operands {
+                    self.expr(value, line);
+                }
+            }
+            Body::Invoke { name, args } => {
+                let error = |message| AsmError {
+                    line,
+                    column,
+                    message,
+                };
+                let expanded = match self.macros.get(&name) {
+                    Some(mac) if mac.params.len() != args.len() => {
+                        return Err(error(format!(
+                            "macro {} takes {} arguments, found {}",
+                            name,
+                            mac.params.len(),
+                            args.len()
+                        )));
+                    }
+                    Some(mac) => {
+                        let params = mac
+                            .params
+                            .iter()
+                            .map(String::as_str)
+                            .zip(args.iter().map(String::as_str))
+                            .collect();
+                        let unique = format!("_{}", self.expansions);
+                        mac.body
+                            .iter()
+                            .map(|(line, text)| (*line, substitute(text, &params, &unique)))
+                            .collect::<Vec<_>>()
+                    }
+                    None => return Err(error(format!("unknown mnemonic or macro `{}`", name))),
+                };
+                if depth == MAX_MACRO_DEPTH {
+                    return Err(error(format!("macro {} nests too deep", name)));
+                }
+                self.expansions += 1;
+                let outermost = self.site.is_none();
+                if outermost {
+                    self.site = Some((line, column));
+                }
+                let result = expanded
+                    .iter()
+                    .try_for_each(|(line, text)| self.line(text, *line, depth + 1));
+                if outermost {
+                    self.site = None;
+                }
+                result.map_err(|err| error(format!("in macro {}: {}", name, err.message)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: Expr, line: usize) {
+        let (line, column) = self.position(line, expr.column);
+        let pending = match expr.terms.as_slice() {
+            [(sign, Term::Number(value))] => Pending::Word(sign * value),
+            _ => Pending::Expr(Expr { column, ..expr }, line),
+        };
+        self.words.push(pending);
+    }
+
+    fn define_macro(
+        &mut self,
+        header: &str,
+        line: usize,
+        lines: &mut dyn Iterator<Item = (usize, &str)>,
+    ) -> Result<(), AsmError> {
+        let mut parser = Parser::new(header, line);
+        parser.ident();
+        let column = parser.column() + 1;
+        let name = match parser.ident() {
+            Some(name) => name,
+            None => return parser.error(column, "expected a macro name".to_string()),
+        };
+        if name.eq_ignore_ascii_case("data") || find_opcode(&name).is_some() {
+            return parser.error(column, format!("macro can not be named `{}`", name));
+        }
+        let params = parser.list(|parser| match parser.ident() {
+            Some(param) => Ok(param),
+            None => parser.error(parser.column(), "expected a parameter name".to_string()),
+        })?;
+
+        let mut body = Vec::new();
+        for (body_line, text) in lines {
+            let first = strip_comment(text).split_whitespace().next().unwrap_or("");
+            if first.eq_ignore_ascii_case("endm") {
+                if self
+                    .macros
+                    .insert(name.clone(), Macro { params, body })
+                    .is_some()
+                {
+                    return parser.error(column, format!("duplicate macro `{}`", name));
+                }
+                return Ok(());
+            }
+            if first.eq_ignore_ascii_case("macro") {
+                return Err(AsmError {
+                    line: body_line,
+                    column: text.find(first).unwrap_or(0) + 1,
+                    message: "macros can not be defined inside a macro".to_string(),
+                });
+            }
+            body.push((body_line, text.to_string()));
+        }
+        parser.error(1, format!("macro {} is missing `endm`", name))
+    }
+
+    fn resolve(self) -> Result<Vec<i64>, AsmError> {
+        let labels = self.labels;
+        self.words
+            .into_iter()
+            .map(|pending| match pending {
+                Pending::Word(word) => Ok(word),
+                Pending::Expr(expr, line) => {
+                    let mut value = 0i64;
+                    for (sign, term) in &expr.terms {
+                        let term = match term {
+                            Term::Number(number) => *number,
+                            Term::Label(name) => match labels.get(name) {
+                                Some(&address) => address as i64,
+                                None => {
+                                    return Err(AsmError {
+                                        line,
+                                        column: expr.column,
+                                        message: format!("undefined label `{}`", name),
+                                    })
+                                }
+                            },
+                        };
+                        value = value.wrapping_add(sign * term);
+                    }
+                    Ok(value)
+                }
+            })
+            .collect()
+    }
+}
+
+//Assembles source text into a memory image that Computer::new can load.
+pub fn assemble(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut assembler = Assembler::default();
+    let mut lines = source.lines().enumerate().map(|(i, text)| (i + 1, text));
+    while let Some((line, text)) = lines.next() {
+        let first = strip_comment(text).split_whitespace().next().unwrap_or("");
+        if first.eq_ignore_ascii_case("macro") {
+            assembler.define_macro(text, line, &mut lines)?;
+        } else if first.eq_ignore_ascii_case("endm") {
+            return Err(AsmError {
+                line,
+                column: text.find(first).unwrap_or(0) + 1,
+                message: "`endm` without `macro`".to_string(),
+            });
+        } else {
+            assembler.line(text, line, 0)?;
+        }
+    }
+    assembler.resolve()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::disasm::disassemble;
+
+    #[test]
+    fn labels_and_data() {
+        let source = "
+            ; Outputs the input doubled
+            start:  IN [value]
+                    MUL [value], #2, [value]
+                    OUT [value]
+                    JT #1, #end
+            value:  data 0
+            end:    halt
+        ";
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![3, 11, 1002, 11, 2, 11, 4, 11, 1105, 1, 12, 0, 99]
+        );
+    }
+
+    #[test]
+    fn relative_operands() {
+        let source = "ARB #10\nADD rb+1, rb-2, rb\nOUT [table+1]\nHALT\ntable: DATA -1, table";
+        assert_eq!(
+            assemble(source).unwrap(),
+            vec![109, 10, 22201, 1, -2, 0, 4, 10, 99, -1, 9]
+        );
+    }
+
+    #[test]
+    fn macros() {
+        let source = "
+            macro copy from, to
+                ADD from, #0, to
+            endm
+            macro countdown n
+                    copy #n, [counter]
+            loop@@: OUT [counter]
+                    ADD [counter], #-1, [counter]
+                    JT [counter], #loop@@
+            endm
+            countdown 3
+            countdown 1
+            HALT
+            counter: DATA 0
+        ";
+        let image = assemble(source).unwrap();
+        assert_eq!(&image[..4], &[1101, 3, 0, 27]);
+        let mut computer = crate::Computer::new(&image);
+        let mut output = Vec::new();
+        computer
+            .run(&mut std::collections::VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![3, 2, 1, 1]);
+    }
+
+    #[test]
+    fn errors_have_positions() {
+        let err = assemble("ADD [1], #2, [3]\n  FOO #1").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 3));
+        let err = assemble("IN #4").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+        let err = assemble("OUT [missing]").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 6));
+        let err = assemble("OUT [1], [2]").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 1));
+        let err = assemble("macro m a\nOUT a\nendm\n\n   m [x]").unwrap_err();
+        assert_eq!((err.line, err.column), (5, 4));
+        assert_eq!(err.message, "undefined label `x`");
+        let err = assemble("0000: HALT\n0002: HALT").unwrap_err();
+        assert_eq!((err.line, err.column), (2, 1));
+        let err = assemble("OUT(1004) #7").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 5));
+        assert_eq!(err.message, "instruction word 1004 does not fit OUT");
+    }
+
+    #[test]
+    fn roundtrip_with_disassembler() {
+        let image = vec![
+            3,
+            100,
+            1002,
+            100,
+            3,
+            100,
+            21101,
+            -2,
+            7,
+            3,
+            204,
+            -1,
+            1105,
+            1,
+            19,
+            1099,
+            42,
+            -7,
+            0,
+            1207,
+            100,
+            5,
+            101,
+            1008,
+            101,
+            0,
+            102,
+            2106,
+            0,
+            12,
+            109,
+            5,
+            99,
+            204,
+            i64::MIN,
+            2,
+            3,
+            i64::MIN,
+        ];
+        let listing = disassemble(&image)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(assemble(&listing).unwrap(), image);
+    }
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use common::fail;
+use intcode::ascii::{Ascii, Text};
+use intcode::Computer;
+
+const USAGE: &str = "usage: ascii [--script <file>] <program>";
+
+fn show(text: Vec<Text>) {
+    for text in text {
+        match text {
+            Text::Line(line) => println!("{}", line),
+            Text::Value(value) => println!("{}", value),
+        }
+    }
+    io::stdout().flush().unwrap();
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (script, path) = match args.as_slice() {
+        [path] => (None, path),
+        [flag, script, path] if flag == "--script" => (Some(script), path),
+        _ => common::usage(USAGE),
+    };
+    let program = common::load(path);
+    //Script lines are sent first, then the terminal takes over
+    let mut script = match script {
+        Some(script) => fs::read_to_string(script)
+            .unwrap_or_else(|err| fail(format!("{}: {}", script, err)))
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>(),
+        None => Vec::new(),
+    }
+    .into_iter();
+
+    let mut ascii = Ascii::new(Computer::new(&program));
+    let stdin = io::stdin();
+    loop {
+        let (text, fault) = ascii.resume();
+        show(text);
+        if let Some(err) = fault {
+            fail(err);
+        }
+        if ascii.halted() {
+            break;
+        }
+        let line = match script.next() {
+            Some(line) => {
+                println!("{}", line);
+                line
+            }
+            None => {
+                let mut line = String::new();
+                if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                    fail("program still waits for input");
+                }
+                line.trim_end_matches(&['\r', '\n'][..]).to_string()
+            }
+        };
+        if let Err(err) = ascii.send_line(&line) {
+            eprintln!("{}, the line was not sent", err);
+        }
+    }
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use std::env;
+use std::fs;
+
+use intcode::asm::assemble;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => common::usage("usage: asm <source>"),
+    };
+    let source =
+        fs::read_to_string(&path).unwrap_or_else(|err| common::fail(format!("{}: {}", path, err)));
+    match assemble(&source) {
+        Ok(image) => println!(
+            "{}",
+            image
+                .iter()
+                .map(|word| word.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        ),
+        Err(err) => common::fail(format!("{}:{}", path, err)),
+    }
+}
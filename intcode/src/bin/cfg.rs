@@ -0,0 +1,21 @@
+mod common;
+
+use std::env;
+
+use intcode::cfg::build;
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let (json, path) = match args.as_slice() {
+        [path] => (false, path),
+        [flag, path] if flag == "--json" => (true, path),
+        _ => common::usage("usage: cfg [--json] <program>"),
+    };
+    let image = common::load(path);
+    let cfg = build(&image);
+    if json {
+        println!("{}", cfg.to_json());
+    } else {
+        print!("{}", cfg.to_dot());
+    }
+}
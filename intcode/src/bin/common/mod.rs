@@ -0,0 +1,43 @@
+//Helpers shared by the command-line tools. Messages go to stderr, wrong usage
+//exits with 2 and everything else that goes wrong with 1. Every binary builds
+//its own copy and only uses some of them.
+#![allow(dead_code)]
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::process;
+
+use intcode::loader;
+
+pub fn exit(code: i32, message: impl Display) -> ! {
+    eprintln!("{}", message);
+    process::exit(code);
+}
+
+pub fn fail(message: impl Display) -> ! {
+    exit(1, message)
+}
+
+pub fn usage(usage: &str) -> ! {
+    exit(2, usage)
+}
+
+//Value of an option like --input, the usage is shown if it is missing.
+pub fn value<S: ToString>(args: &mut impl Iterator<Item = S>, usage_text: &str) -> String {
+    match args.next() {
+        Some(value) => value.to_string(),
+        None => usage(usage_text),
+    }
+}
+
+//Values given with --input, separated like a program.
+pub fn parse_input(values: &str) -> VecDeque<i64> {
+    match loader::parse(values) {
+        Ok(values) => values.into(),
+        Err(err) => exit(2, format!("--input: {}", err)),
+    }
+}
+
+pub fn load(path: &str) -> Vec<i64> {
+    loader::load(path).unwrap_or_else(|err| fail(format!("{}: {}", path, err)))
+}
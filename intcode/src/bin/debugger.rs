@@ -0,0 +1,205 @@
+mod common;
+
+use std::env;
+use std::io::{self, BufRead, Write};
+use std::time::Duration;
+
+use intcode::debugger::{Debugger, Stop};
+use intcode::limits::{Limits, Reason};
+use intcode::Computer;
+
+const HELP: &str = "\
+s, step [n]         execute n instructions (default 1)
+n, next             step over the current instruction
+c, continue         run until a breakpoint, watchpoint, missing input or halt,
+                    endless loops and runs over 5 seconds stop as well
+b, break <addr>     set a breakpoint, without address list them
+d, delete <addr>    remove a breakpoint
+w, watch <addr>     stop when the value at addr changes, without address list them
+u, unwatch <addr>   remove a watchpoint
+r, regs             show pc, relative base and pending input
+x, mem <addr> [n]   dump n words of memory (default 16)
+set <addr> <value>  write a value to memory
+l, list [n]         disassemble n lines around pc (default 5)
+i, input <v>...     queue input values
+q, quit             leave the debugger
+An empty line repeats the last command.";
+
+fn parse_args(args: &[&str]) -> Result<Vec<i64>, String> {
+    args.iter()
+        .map(|arg| {
+            arg.parse::<i64>()
+                .map_err(|_| format!("{} is no number", arg))
+        })
+        .collect()
+}
+
+fn address(args: &[i64], index: usize) -> Result<usize, String> {
+    match args.get(index) {
+        Some(&value) if value >= 0 => Ok(value as usize),
+        Some(value) => Err(format!("{} is no valid address", value)),
+        None => Err("missing address".to_string()),
+    }
+}
+
+fn print_listing(debugger: &Debugger, before: usize, after: usize) {
+    let pc = debugger.computer().pc();
+    for line in debugger.listing(before, after) {
+        let marker = if line.address() == pc { "=>" } else { "  " };
+        println!("{} {}", marker, line);
+    }
+}
+
+fn report(debugger: &mut Debugger, stop: Result<Stop, intcode::IntcodeError>) {
+    for value in debugger.take_output() {
+        println!("output: {}", value);
+    }
+    match stop {
+        Ok(Stop::Stepped) => {}
+        Ok(Stop::Breakpoint(address)) => println!("breakpoint at {}", address),
+        Ok(Stop::Watchpoint { address, old, new }) => {
+            println!("watchpoint [{}]: {} -> {}", address, old, new)
+        }
+        Ok(Stop::NeedsInput) => println!("program waits for input, queue some with `input`"),
+        Ok(Stop::Halted) => println!("program halted"),
+        Ok(Stop::Limit(Reason::Cycle { length })) => {
+            println!(
+                "program loops forever, the state repeats every {} instructions",
+                length
+            )
+        }
+        Ok(Stop::Limit(_)) => println!("still running, continue to go on"),
+        Err(err) => println!("{}", err),
+    }
+    print_listing(debugger, 0, 1);
+}
+
+fn execute(debugger: &mut Debugger, command: &str, args: &[i64]) -> Result<bool, String> {
+    match command {
+        "s" | "step" => {
+            let count = args.first().copied().unwrap_or(1).max(1);
+            let mut stop = Ok(Stop::Stepped);
+            for _ in 0..count {
+                stop = debugger.step();
+                if !matches!(stop, Ok(Stop::Stepped)) {
+                    break;
+                }
+            }
+            report(debugger, stop);
+        }
+        "n" | "next" => {
+            let stop = debugger.step_over();
+            report(debugger, stop);
+        }
+        "c" | "continue" => {
+            let stop = debugger.cont();
+            report(debugger, stop);
+        }
+        "b" | "break" if args.is_empty() => {
+            for address in debugger.breakpoints() {
+                println!("breakpoint at {}", address);
+            }
+        }
+        "b" | "break" => {
+            debugger.add_breakpoint(address(args, 0)?);
+        }
+        "d" | "delete" => {
+            if !debugger.remove_breakpoint(address(args, 0)?) {
+                return Err("no such breakpoint".to_string());
+            }
+        }
+        "w" | "watch" if args.is_empty() => {
+            for address in debugger.watchpoints() {
+                println!("watchpoint at {}", address);
+            }
+        }
+        "w" | "watch" => {
+            debugger.add_watchpoint(address(args, 0)?);
+        }
+        "u" | "unwatch" => {
+            if !debugger.remove_watchpoint(address(args, 0)?) {
+                return Err("no such watchpoint".to_string());
+            }
+        }
+        "r" | "regs" => {
+            let computer = debugger.computer();
+            println!("pc: {}", computer.pc());
+            println!("rb: {}", computer.relative_base());
+            println!("input: {:?}", computer.pending_input());
+            print_listing(debugger, 0, 1);
+        }
+        "x" | "mem" => {
+            let start = address(args, 0)?;
+            let count = args.get(1).copied().unwrap_or(16).max(0) as usize;
+            let memory = debugger.computer().memory();
+            for row in (start..start + count).step_by(8) {
+                let words = (row..(row + 8).min(start + count))
+                    .map(|address| format!("{:>8}", memory[address]))
+                    .collect::<String>();
+                println!("{:04}:{}", row, words);
+            }
+        }
+        "set" => {
+            let target = address(args, 0)?;
+            let value = *args.get(1).ok_or("missing value")?;
+            debugger.computer_mut().memory_mut()[target] = value;
+        }
+        "l" | "list" => {
+            let count = args.first().copied().unwrap_or(5).max(1) as usize;
+            print_listing(debugger, count * 2, count);
+        }
+        "i" | "input" => {
+            if args.is_empty() {
+                return Err("missing value".to_string());
+            }
+            for &value in args {
+                debugger.computer_mut().push_input(value);
+            }
+        }
+        "h" | "help" => println!("{}", HELP),
+        "q" | "quit" => return Ok(false),
+        other => return Err(format!("unknown command {}, try help", other)),
+    }
+    Ok(true)
+}
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => common::usage("usage: debugger <program>"),
+    };
+    let program = common::load(&path);
+    let mut debugger = Debugger::new(Computer::new(&program));
+    debugger.set_limits(Limits {
+        time: Some(Duration::from_secs(5)),
+        detect_cycles: true,
+        ..Limits::default()
+    });
+    print_listing(&debugger, 0, 1);
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(dbg) ");
+        io::stdout().flush().unwrap();
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+            break;
+        }
+        if line.trim().is_empty() {
+            line = last.clone();
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (command, args) = match words.split_first() {
+            Some((command, args)) => (*command, args),
+            None => continue,
+        };
+        last = line.clone();
+        let result = parse_args(args).and_then(|args| execute(&mut debugger, command, &args));
+        match result {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(err) => println!("{}", err),
+        }
+    }
+}
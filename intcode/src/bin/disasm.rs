@@ -0,0 +1,16 @@
+mod common;
+
+use std::env;
+
+use intcode::disasm::disassemble;
+
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => common::usage("usage: disasm <program>"),
+    };
+    let image = common::load(&path);
+    for line in disassemble(&image) {
+        println!("{}", line);
+    }
+}
@@ -0,0 +1,202 @@
+mod common;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::process;
+
+use intcode::device::{Input, LineReader, OutputFn};
+use intcode::{loader, Computer};
+
+const USAGE: &str = "usage: intcode [--input 1,2,... | --input-file <file>] [--patch addr=value]...
+               [--dump-memory <file> | -] [--output json|lines] [<program> | -]
+
+The program is read from stdin if no path or - is given. Without --input or
+--input-file the inputs are read from stdin, one per line.
+--dump-memory writes the memory at exit as a program. With - it goes to stdout,
+as a last line after the outputs or as the memory field of the json object.
+Dumps are limited to 16777216 words, larger memory is refused with status 2.
+Exits with 0 if the program halts, 1 on a fault and 2 on bad arguments or input.";
+
+//Dumps are dense, so a single far write could need terabytes.
+const MAX_DUMP: usize = 1 << 24;
+
+fn memory_image(computer: &Computer) -> Result<Vec<i64>, String> {
+    let extent = computer.memory().extent();
+    if extent > MAX_DUMP {
+        return Err(format!(
+            "--dump-memory: memory reaches address {}, dumps are limited to {} words",
+            extent - 1,
+            MAX_DUMP
+        ));
+    }
+    Ok(computer.memory().image())
+}
+
+fn parse_patch(patch: &str) -> Option<(usize, i64)> {
+    let mut parts = patch.splitn(2, '=');
+    let address = parts.next()?.trim().parse().ok()?;
+    let value = parts.next()?.trim().parse().ok()?;
+    Some((address, value))
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+fn program_line(values: &[i64]) -> String {
+    let values = values.iter().map(i64::to_string).collect::<Vec<_>>();
+    values.join(",")
+}
+
+fn json_list(values: &[i64]) -> String {
+    format!("[{}]", program_line(values))
+}
+
+fn main() {
+    let mut input = None;
+    let mut patches = Vec::new();
+    let mut dump_memory = None;
+    let mut json = false;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let values = common::value(&mut args, USAGE);
+                input = Some(common::parse_input(&values));
+            }
+            "--input-file" => {
+                let file = common::value(&mut args, USAGE);
+                input = Some(
+                    loader::load(&file)
+                        .unwrap_or_else(|err| common::exit(2, format!("{}: {}", file, err)))
+                        .into(),
+                );
+            }
+            "--patch" => {
+                let patch = common::value(&mut args, USAGE);
+                patches.push(parse_patch(&patch).unwrap_or_else(|| {
+                    common::exit(2, format!("--patch: {:?} is no addr=value", patch))
+                }));
+            }
+            "--dump-memory" => {
+                dump_memory = Some(common::value(&mut args, USAGE));
+            }
+            "--output" => match args.next().as_deref() {
+                Some("json") => json = true,
+                Some("lines") => json = false,
+                _ => common::usage(USAGE),
+            },
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && (arg == "-" || !arg.starts_with('-')) => path = Some(arg),
+            _ => common::usage(USAGE),
+        }
+    }
+    let from_stdin = path.as_deref().is_none_or(|path| path == "-");
+    let program = if from_stdin {
+        loader::read(io::stdin().lock())
+            .unwrap_or_else(|err| common::exit(2, format!("stdin: {}", err)))
+    } else {
+        let path = path.unwrap();
+        loader::load(&path).unwrap_or_else(|err| common::exit(2, format!("{}: {}", path, err)))
+    };
+
+    let mut computer = Computer::new(&program);
+    for (address, value) in patches {
+        computer.memory_mut()[address] = value;
+    }
+    let stdin = io::stdin();
+    let mut input: Box<dyn Input> = match input {
+        Some(values) => Box::new(values),
+        //The program already used up stdin
+        None if from_stdin => Box::new(VecDeque::new()),
+        None => Box::new(LineReader::new(stdin.lock())),
+    };
+    //Lines are printed as soon as they are produced, json collects everything
+    let stdout = io::stdout();
+    let mut stdout = BufWriter::new(stdout.lock());
+    let mut values = Vec::new();
+    let result = computer.run(
+        &mut *input,
+        &mut OutputFn(|value| {
+            if json {
+                values.push(value);
+            } else {
+                writeln!(stdout, "{}", value)
+                    .and_then(|_| stdout.flush())
+                    .unwrap_or_else(|err| common::exit(2, err));
+            }
+        }),
+    );
+    //The image is only built if a dump was asked for
+    let mut memory_to_stdout = None;
+    if let Some(file) = &dump_memory {
+        let memory = memory_image(&computer).unwrap_or_else(|err| common::exit(2, err));
+        if file == "-" {
+            memory_to_stdout = Some(memory);
+        } else if let Err(err) = fs::write(file, format!("{}\n", program_line(&memory))) {
+            common::exit(2, format!("{}: {}", file, err));
+        }
+    }
+
+    if json {
+        let mut fields = match &result {
+            Ok(value) => vec![
+                r#""status":"halted""#.to_string(),
+                format!(r#""result":{}"#, value),
+            ],
+            Err(err) => vec![
+                r#""status":"fault""#.to_string(),
+                format!(r#""error":{}"#, json_string(&err.to_string())),
+            ],
+        };
+        fields.push(format!(r#""output":{}"#, json_list(&values)));
+        if let Some(memory) = &memory_to_stdout {
+            fields.push(format!(r#""memory":{}"#, json_list(memory)));
+        }
+        writeln!(stdout, "{{{}}}", fields.join(",")).unwrap_or_else(|err| common::exit(2, err));
+    } else if let Some(memory) = &memory_to_stdout {
+        writeln!(stdout, "{}", program_line(memory)).unwrap_or_else(|err| common::exit(2, err));
+    }
+    drop(stdout);
+    if let Err(err) = result {
+        if !json {
+            eprintln!("{}", err);
+        }
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn far_writes() {
+        let mut computer =
+            Computer::new(&[1101, 3, 4, 1_000_000_000_000, 4, 1_000_000_000_000, 99]);
+        let mut output = Vec::new();
+        computer.run(&mut VecDeque::new(), &mut output).unwrap();
+        assert_eq!(output, vec![7]);
+        assert!(memory_image(&computer).is_err());
+
+        let computer = Computer::new(&[1, 0, 3]);
+        assert_eq!(memory_image(&computer), Ok(vec![1, 0, 3]));
+    }
+}
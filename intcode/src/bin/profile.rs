@@ -0,0 +1,48 @@
+mod common;
+
+use std::collections::VecDeque;
+use std::env;
+
+use common::fail;
+use intcode::Computer;
+
+const USAGE: &str = "usage: profile [--input 1,2,...] [--top n] <program>";
+
+fn main() {
+    let mut input = VecDeque::new();
+    let mut top = 10;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                let values = common::value(&mut args, USAGE);
+                input = common::parse_input(&values);
+            }
+            "--top" => {
+                top = args
+                    .next()
+                    .and_then(|top| top.parse().ok())
+                    .unwrap_or_else(|| common::usage(USAGE));
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => common::usage(USAGE),
+        }
+    }
+    let path = path.unwrap_or_else(|| common::usage(USAGE));
+    let program = common::load(&path);
+
+    let mut computer = Computer::new(&program);
+    computer.enable_profiling();
+    let mut output = Vec::new();
+    let result = computer.run(&mut input, &mut output);
+    for value in output {
+        println!("{}", value);
+    }
+    //The report is printed on faults as well, that is often when it is needed most
+    let profile = computer.take_profile().unwrap();
+    eprint!("{}", profile.report(top));
+    if let Err(err) = result {
+        fail(err);
+    }
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::collections::VecDeque;
+use std::env;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+
+use common::fail;
+use intcode::trace::{replay, BinaryTracer, JsonTracer, TraceReader, Tracer};
+use intcode::Computer;
+
+const USAGE: &str = "\
+usage: trace record [--json] [--input 1,2,...] <program> <trace>
+       trace replay <program> <trace>";
+
+fn record(args: &[String]) {
+    let mut json = false;
+    let mut input = VecDeque::new();
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--input" => {
+                let values = common::value(&mut args, USAGE);
+                input = common::parse_input(&values);
+            }
+            path => paths.push(path),
+        }
+    }
+    let (program, out) = match paths.as_slice() {
+        [program, out] => (common::load(program), out),
+        _ => common::usage(USAGE),
+    };
+
+    let file = File::create(out).unwrap_or_else(|err| fail(format!("{}: {}", out, err)));
+    let tracer: Box<dyn Tracer + Send> = if json {
+        Box::new(JsonTracer::new(BufWriter::new(file)))
+    } else {
+        Box::new(BinaryTracer::new(BufWriter::new(file)))
+    };
+    let mut computer = Computer::new(&program);
+    computer.set_tracer(tracer);
+    let mut output = Vec::new();
+    let result = computer.run(&mut input, &mut output);
+    //Faulting runs are the ones worth looking at, so the trace is written either way
+    if let Err(err) = computer.take_tracer().unwrap().flush() {
+        fail(format!("{}: {}", out, err));
+    }
+    for value in output {
+        println!("{}", value);
+    }
+    if let Err(err) = result {
+        fail(err);
+    }
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.split_first() {
+        Some((command, rest)) if command == "record" => record(rest),
+        Some((command, rest)) if command == "replay" && rest.len() == 2 => {
+            let program = common::load(&rest[0]);
+            let file =
+                File::open(&rest[1]).unwrap_or_else(|err| fail(format!("{}: {}", rest[1], err)));
+            let trace = TraceReader::new(BufReader::new(file))
+                .unwrap_or_else(|err| fail(format!("{}: {}", rest[1], err)));
+            match replay(&program, trace) {
+                Ok(steps) => println!("{} steps in lockstep", steps),
+                Err(err) => fail(err),
+            }
+        }
+        _ => common::usage(USAGE),
+    }
+}
@@ -0,0 +1,87 @@
+use crate::computer::Mode;
+use crate::word::Word;
+
+//Instructions starting above this address are decoded every time instead of
+//growing the cache without bound.
+const CACHE_LIMIT: usize = 1 << 16;
+const MAX_SIZE: usize = 4;
+
+//An instruction word split into opcode and modes, together with the raw
+//parameter words that follow it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct Decoded<W> {
+    pub opcode: i64,
+    pub modes: [Mode; 3],
+    pub params: [W; 3],
+    pub size: usize,
+}
+
+//Decoded instructions keyed by their address. Every write to memory has to be
+//reported with invalidate so changed code is decoded again.
+#[derive(Default)]
+pub(crate) struct DecodeCache<W> {
+    entries: Vec<Option<Decoded<W>>>,
+}
+
+impl<W: Word> DecodeCache<W> {
+    pub fn get(&self, pc: usize) -> Option<Decoded<W>> {
+        self.entries.get(pc).copied().flatten()
+    }
+
+    pub fn insert(&mut self, pc: usize, decoded: Decoded<W>) {
+        if pc >= CACHE_LIMIT {
+            return;
+        }
+        if self.entries.len() <= pc {
+            self.entries.resize(pc + 1, None);
+        }
+        self.entries[pc] = Some(decoded);
+    }
+
+    //Drops every cached instruction that covers address.
+    pub fn invalidate(&mut self, address: usize) {
+        for start in address.saturating_sub(MAX_SIZE - 1)..=address {
+            if let Some(entry) = self.entries.get_mut(start) {
+                if entry.is_some_and(|decoded| start + decoded.size > address) {
+                    *entry = None;
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn writes_invalidate_covering_instructions() {
+        let add = Decoded {
+            opcode: 1,
+            modes: [Mode::Position; 3],
+            params: [5i64, 6, 7],
+            size: 4,
+        };
+        let halt = Decoded {
+            opcode: 99,
+            modes: [Mode::Position; 3],
+            params: [0; 3],
+            size: 1,
+        };
+        let mut cache = DecodeCache::default();
+        cache.insert(0, add);
+        cache.insert(4, halt);
+        cache.invalidate(5);
+        assert_eq!(cache.get(0), Some(add));
+        assert_eq!(cache.get(4), Some(halt));
+        cache.invalidate(3);
+        assert_eq!(cache.get(0), None);
+        cache.invalidate(4);
+        assert_eq!(cache.get(4), None);
+        cache.invalidate(1 << 40);
+    }
+}
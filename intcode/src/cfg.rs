@@ -0,0 +1,288 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use crate::disasm::{decode, Line, Operand};
+use crate::word::Word;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EdgeKind {
+    FallThrough,
+    Jump,
+    //Jump whose target is only known at runtime
+    Unknown,
+}
+
+impl EdgeKind {
+    fn name(self) -> &'static str {
+        match self {
+            EdgeKind::FallThrough => "fall",
+            EdgeKind::Jump => "jump",
+            EdgeKind::Unknown => "unknown",
+        }
+    }
+}
+
+//Edges connect block start addresses. Unknown edges have no target.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Edge {
+    pub from: usize,
+    pub to: Option<usize>,
+    pub kind: EdgeKind,
+}
+
+//Straight-line code that is only entered at start. A reachable word that does
+//not decode ends up as a data line and ends its block.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+}
+
+impl Block {
+    //First address after the block.
+    pub fn end(&self) -> usize {
+        self.lines
+            .last()
+            .map_or(self.start, |line| line.address() + line.size())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Cfg {
+    pub blocks: Vec<Block>,
+    pub edges: Vec<Edge>,
+}
+
+fn successors(line: &Line) -> Vec<(EdgeKind, Option<usize>)> {
+    let next = line.address() + line.size();
+    let (mnemonic, operands) = match line {
+        Line::Instruction {
+            mnemonic, operands, ..
+        } => (*mnemonic, operands),
+        Line::Data { .. } => return vec![],
+    };
+    match mnemonic {
+        "HALT" => vec![],
+        "JT" | "JF" => {
+            //An immediate condition decides the jump statically
+            let (taken, falls) = match operands[0] {
+                Operand::Immediate(value) => {
+                    let jumps = (value != 0) == (mnemonic == "JT");
+                    (jumps, !jumps)
+                }
+                _ => (true, true),
+            };
+            let mut edges = Vec::new();
+            if taken {
+                edges.push(match operands[1] {
+                    Operand::Immediate(to) => match to.to_address() {
+                        Ok(to) => (EdgeKind::Jump, Some(to)),
+                        Err(_) => (EdgeKind::Unknown, None),
+                    },
+                    _ => (EdgeKind::Unknown, None),
+                });
+            }
+            if falls {
+                edges.push((EdgeKind::FallThrough, Some(next)));
+            }
+            edges
+        }
+        _ => vec![(EdgeKind::FallThrough, Some(next))],
+    }
+}
+
+fn is_plain(successors: &[(EdgeKind, Option<usize>)]) -> bool {
+    matches!(successors, [(EdgeKind::FallThrough, _)])
+}
+
+//Walks the program statically from address 0. Code that rewrites itself is
+//only followed as far as the initial image tells.
+pub fn build(image: &[i64]) -> Cfg {
+    let mut lines = BTreeMap::new();
+    let mut leaders = BTreeSet::new();
+    //Number of plain instructions running into every address
+    let mut entered = BTreeMap::new();
+    leaders.insert(0);
+    let mut work = vec![0];
+    while let Some(address) = work.pop() {
+        if lines.contains_key(&address) {
+            continue;
+        }
+        let line = decode(image, address).unwrap_or(Line::Data {
+            address,
+            value: image.get(address).copied().unwrap_or(0),
+        });
+        let successors = successors(&line);
+        let plain = is_plain(&successors);
+        for &(_, to) in &successors {
+            if let Some(to) = to {
+                if plain {
+                    *entered.entry(to).or_insert(0) += 1;
+                } else {
+                    leaders.insert(to);
+                }
+                work.push(to);
+            }
+        }
+        lines.insert(address, line);
+    }
+
+    let starts = lines
+        .keys()
+        .copied()
+        .filter(|address| leaders.contains(address) || entered.get(address) != Some(&1))
+        .collect::<BTreeSet<_>>();
+    let mut blocks = Vec::new();
+    let mut edges = Vec::new();
+    for &start in &starts {
+        let mut block = Block {
+            start,
+            lines: Vec::new(),
+        };
+        let mut address = start;
+        loop {
+            let line = lines[&address].clone();
+            let successors = successors(&line);
+            address += line.size();
+            block.lines.push(line);
+            if !is_plain(&successors) || starts.contains(&address) {
+                edges.extend(successors.into_iter().map(|(kind, to)| Edge {
+                    from: start,
+                    to,
+                    kind,
+                }));
+                break;
+            }
+        }
+        blocks.push(block);
+    }
+    Cfg { blocks, edges }
+}
+
+impl Cfg {
+    //Graphviz graph with one box per block. Unknown edges point to a single
+    //node named unknown.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph cfg {\n    node [shape=box, fontname=monospace];\n");
+        for block in &self.blocks {
+            let label = block
+                .lines
+                .iter()
+                .map(|line| format!("{}\\l", line))
+                .collect::<String>();
+            writeln!(dot, "    b{} [label=\"{}\"];", block.start, label).unwrap();
+        }
+        if self.edges.iter().any(|edge| edge.to.is_none()) {
+            dot.push_str("    unknown [label=\"?\", shape=diamond];\n");
+        }
+        for edge in &self.edges {
+            match edge.to {
+                Some(to) => writeln!(
+                    dot,
+                    "    b{} -> b{} [label={}];",
+                    edge.from,
+                    to,
+                    edge.kind.name()
+                ),
+                None => writeln!(dot, "    b{} -> unknown [style=dashed];", edge.from),
+            }
+            .unwrap();
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> String {
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|block| {
+                let lines = block
+                    .lines
+                    .iter()
+                    .map(|line| format!("\"{}\"", line))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                format!(
+                    r#"{{"start":{},"end":{},"lines":[{}]}}"#,
+                    block.start,
+                    block.end(),
+                    lines
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        let edges = self
+            .edges
+            .iter()
+            .map(|edge| {
+                let to = edge.to.map_or("null".to_string(), |to| to.to_string());
+                format!(
+                    r#"{{"from":{},"to":{},"kind":"{}"}}"#,
+                    edge.from,
+                    to,
+                    edge.kind.name()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(",");
+        format!(r#"{{"blocks":[{}],"edges":[{}]}}"#, blocks, edges)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures;
+
+    fn edge(from: usize, to: Option<usize>, kind: EdgeKind) -> Edge {
+        Edge { from, to, kind }
+    }
+
+    #[test]
+    fn loop_blocks() {
+        let cfg = build(&fixtures::countdown());
+        let starts = cfg
+            .blocks
+            .iter()
+            .map(|block| (block.start, block.end()))
+            .collect::<Vec<_>>();
+        assert_eq!(starts, vec![(0, 9), (9, 10)]);
+        assert_eq!(
+            cfg.edges,
+            vec![
+                edge(0, Some(0), EdgeKind::Jump),
+                edge(0, Some(9), EdgeKind::FallThrough)
+            ]
+        );
+        assert_eq!(
+            cfg.to_dot(),
+            "digraph cfg {
+    node [shape=box, fontname=monospace];
+    b0 [label=\"0000: OUT [20]\\l0002: ADD [20], #-1, [20]\\l0006: JT [20], #0\\l\"];
+    b9 [label=\"0009: HALT\\l\"];
+    b0 -> b0 [label=jump];
+    b0 -> b9 [label=fall];
+}
+"
+        );
+    }
+
+    #[test]
+    fn unconditional_and_indirect_jumps() {
+        //JT #1, #5 skips the data word, JF [9], [10] jumps through memory
+        let cfg = build(&[1105, 1, 5, 42, 42, 6, 9, 10, 99, 0, 8]);
+        assert_eq!(
+            cfg.edges,
+            vec![
+                edge(0, Some(5), EdgeKind::Jump),
+                edge(5, None, EdgeKind::Unknown),
+                edge(5, Some(8), EdgeKind::FallThrough)
+            ]
+        );
+        assert_eq!(
+            cfg.to_json(),
+            r#"{"blocks":[{"start":0,"end":3,"lines":["0000: JT #1, #5"]},{"start":5,"end":8,"lines":["0005: JF [9], [10]"]},{"start":8,"end":9,"lines":["0008: HALT"]}],"edges":[{"from":0,"to":5,"kind":"jump"},{"from":5,"to":null,"kind":"unknown"},{"from":5,"to":8,"kind":"fall"}]}"#
+        );
+    }
+}
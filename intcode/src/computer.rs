@@ -0,0 +1,945 @@
+use std::collections::VecDeque;
+use std::convert::TryFrom;
+
+use crate::cache::{DecodeCache, Decoded};
+use crate::device::{Input, Output};
+use crate::error::{ErrorKind, IntcodeError};
+use crate::memory::Memory;
+use crate::modification::{Modification, Policy, Tracker};
+use crate::profile::Profile;
+use crate::trace::{TraceRecord, Tracer};
+use crate::word::Word;
+
+//Intcode machine over words of type W, i64 unless a narrower or wider word
+//is needed.
+pub struct Computer<W = i64> {
+    pc: usize,
+    memory: Memory<W>,
+    relative_base: W,
+    input: VecDeque<W>,
+    cache: DecodeCache<W>,
+    arithmetic: Arithmetic,
+    tracing: Option<Tracing<W>>,
+    profile: Option<Profile>,
+    modification: Option<Tracker>,
+}
+
+//Tracer plus the reads and writes of the instruction being executed.
+struct Tracing<W> {
+    tracer: Box<dyn Tracer<W> + Send>,
+    operands: Vec<W>,
+    writes: Vec<(usize, W)>,
+}
+
+//Reason why resume handed control back to the caller.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum State<W = i64> {
+    NeedsInput,
+    Output(W),
+    Halted,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BinaryKind {
+    Multiply,
+    Plus,
+}
+
+//What Binary instructions do with results that do not fit into a word.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Arithmetic {
+    //Fault with ErrorKind::Overflow
+    #[default]
+    Checked,
+    Saturating,
+    Wrapping,
+}
+
+impl Arithmetic {
+    pub fn apply<W: Word>(self, kind: BinaryKind, a: W, b: W) -> Option<W> {
+        Some(match (self, kind) {
+            (Arithmetic::Checked, BinaryKind::Plus) => a.checked_add(b)?,
+            (Arithmetic::Checked, BinaryKind::Multiply) => a.checked_mul(b)?,
+            (Arithmetic::Saturating, BinaryKind::Plus) => a.saturating_add(b),
+            (Arithmetic::Saturating, BinaryKind::Multiply) => a.saturating_mul(b),
+            (Arithmetic::Wrapping, BinaryKind::Plus) => a.wrapping_add(b),
+            (Arithmetic::Wrapping, BinaryKind::Multiply) => a.wrapping_mul(b),
+        })
+    }
+}
+
+//A decoded instruction with all parameters resolved to addresses.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Instruction {
+    Binary {
+        kind: BinaryKind,
+        target: usize,
+        op1: usize,
+        op2: usize,
+    },
+    Input {
+        target: usize,
+    },
+    Output {
+        target: usize,
+    },
+    Halt,
+    Jump {
+        kind: JumpCondition,
+        cond: usize,
+        to: usize,
+    },
+    Comparison {
+        kind: ComparisonKind,
+        target: usize,
+        op1: usize,
+        op2: usize,
+    },
+    AdjustBase {
+        op: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum JumpCondition {
+    True,
+    False,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ComparisonKind {
+    LessThan,
+    Equals,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(crate) enum Mode {
+    Position = 0,
+    Immediate = 1,
+    Relative = 2,
+}
+
+impl TryFrom<i64> for Mode {
+    type Error = ErrorKind;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            x => Err(ErrorKind::BadMode(x)),
+        }
+    }
+}
+
+pub(crate) fn split_instruction<W: Word>(instr: W) -> Result<(i64, Mode, Mode, Mode), ErrorKind> {
+    let instr = instr.to_i128();
+    let inst = (instr % 100) as i64;
+    let op1_mode = Mode::try_from(((instr / 100) % 10) as i64)?;
+    let op2_mode = Mode::try_from(((instr / 1000) % 10) as i64)?;
+    let op3_mode = Mode::try_from(((instr / 10000) % 10) as i64)?;
+    Ok((inst, op1_mode, op2_mode, op3_mode))
+}
+
+//Mnemonic, parameter count and index of the written parameter for every opcode.
+pub(crate) fn opcode_info(opcode: i64) -> Option<(&'static str, usize, Option<usize>)> {
+    match opcode {
+        1 => Some(("ADD", 3, Some(2))),
+        2 => Some(("MUL", 3, Some(2))),
+        3 => Some(("IN", 1, Some(0))),
+        4 => Some(("OUT", 1, None)),
+        5 => Some(("JT", 2, None)),
+        6 => Some(("JF", 2, None)),
+        7 => Some(("LT", 3, Some(2))),
+        8 => Some(("EQ", 3, Some(2))),
+        9 => Some(("ARB", 1, None)),
+        99 => Some(("HALT", 0, None)),
+        _ => None,
+    }
+}
+
+impl Instruction {
+    pub fn opcode(&self) -> i64 {
+        match self {
+            Instruction::Binary {
+                kind: BinaryKind::Plus,
+                ..
+            } => 1,
+            Instruction::Binary {
+                kind: BinaryKind::Multiply,
+                ..
+            } => 2,
+            Instruction::Input { .. } => 3,
+            Instruction::Output { .. } => 4,
+            Instruction::Jump {
+                kind: JumpCondition::True,
+                ..
+            } => 5,
+            Instruction::Jump {
+                kind: JumpCondition::False,
+                ..
+            } => 6,
+            Instruction::Comparison {
+                kind: ComparisonKind::LessThan,
+                ..
+            } => 7,
+            Instruction::Comparison {
+                kind: ComparisonKind::Equals,
+                ..
+            } => 8,
+            Instruction::AdjustBase { .. } => 9,
+            Instruction::Halt => 99,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        opcode_info(self.opcode()).map_or("?", |(mnemonic, _, _)| mnemonic)
+    }
+
+    //Resolved parameter addresses in the order they appear in memory.
+    pub fn addresses(&self) -> Vec<usize> {
+        match *self {
+            Instruction::Binary {
+                target, op1, op2, ..
+            }
+            | Instruction::Comparison {
+                target, op1, op2, ..
+            } => vec![op1, op2, target],
+            Instruction::Input { target } | Instruction::Output { target } => vec![target],
+            Instruction::Jump { cond, to, .. } => vec![cond, to],
+            Instruction::AdjustBase { op } => vec![op],
+            Instruction::Halt => vec![],
+        }
+    }
+
+    //Inverse of opcode and addresses.
+    pub fn from_parts(opcode: i64, addresses: &[usize]) -> Option<Instruction> {
+        let (_, count, _) = opcode_info(opcode)?;
+        if addresses.len() != count {
+            return None;
+        }
+        let a = |i: usize| addresses[i];
+        Some(match opcode {
+            1 | 2 => Instruction::Binary {
+                kind: if opcode == 1 {
+                    BinaryKind::Plus
+                } else {
+                    BinaryKind::Multiply
+                },
+                target: a(2),
+                op1: a(0),
+                op2: a(1),
+            },
+            3 => Instruction::Input { target: a(0) },
+            4 => Instruction::Output { target: a(0) },
+            5 | 6 => Instruction::Jump {
+                kind: if opcode == 5 {
+                    JumpCondition::True
+                } else {
+                    JumpCondition::False
+                },
+                cond: a(0),
+                to: a(1),
+            },
+            7 | 8 => Instruction::Comparison {
+                kind: if opcode == 7 {
+                    ComparisonKind::LessThan
+                } else {
+                    ComparisonKind::Equals
+                },
+                target: a(2),
+                op1: a(0),
+                op2: a(1),
+            },
+            9 => Instruction::AdjustBase { op: a(0) },
+            _ => Instruction::Halt,
+        })
+    }
+}
+
+//Clones share memory pages until one side writes, so forking a machine at every
+//decision point of a search stays cheap. The decode cache is not shared that
+//way, so clones start without one and fill their own. Tracer and profile are
+//not cloned, self modification tracking is, so forks keep its policy.
+impl<W: Word> Clone for Computer<W> {
+    fn clone(&self) -> Self {
+        Computer {
+            pc: self.pc,
+            memory: self.memory.clone(),
+            relative_base: self.relative_base,
+            input: self.input.clone(),
+            cache: DecodeCache::default(),
+            arithmetic: self.arithmetic,
+            tracing: None,
+            profile: None,
+            modification: self.modification.clone(),
+        }
+    }
+}
+
+impl Computer {
+    pub fn new(program: &[i64]) -> Computer {
+        Computer::from_program(program)
+    }
+}
+
+impl<W: Word> Computer<W> {
+    //Like new for any word type, e.g. Computer::<i128>::from_program.
+    pub fn from_program(program: &[W]) -> Computer<W> {
+        Computer {
+            pc: 0,
+            memory: Memory::from(program),
+            relative_base: W::ZERO,
+            input: VecDeque::new(),
+            cache: DecodeCache::default(),
+            arithmetic: Arithmetic::default(),
+            tracing: None,
+            profile: None,
+            modification: None,
+        }
+    }
+
+    pub(crate) fn from_state(
+        pc: usize,
+        relative_base: W,
+        memory: Memory<W>,
+        input: VecDeque<W>,
+        arithmetic: Arithmetic,
+    ) -> Computer<W> {
+        Computer {
+            pc,
+            memory,
+            relative_base,
+            input,
+            cache: DecodeCache::default(),
+            arithmetic,
+            tracing: None,
+            profile: None,
+            modification: None,
+        }
+    }
+
+    //Every executed instruction is handed to the tracer until it is taken again.
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer<W> + Send>) {
+        self.tracing = Some(Tracing {
+            tracer,
+            operands: Vec::new(),
+            writes: Vec::new(),
+        });
+    }
+
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    pub fn take_tracer(&mut self) -> Option<Box<dyn Tracer<W> + Send>> {
+        self.tracing.take().map(|tracing| tracing.tracer)
+    }
+
+    //Starts counting executed instructions and memory accesses from scratch.
+    pub fn enable_profiling(&mut self) {
+        self.profile = Some(Profile::default());
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn take_profile(&mut self) -> Option<Profile> {
+        self.profile.take()
+    }
+
+    //Starts watching for writes to executed code and execution of written
+    //words. Code loaded before is not considered written.
+    pub fn track_self_modification(&mut self, policy: Policy) {
+        self.modification = Some(Tracker::new(policy));
+    }
+
+    //Self modifications found in report mode.
+    pub fn modifications(&self) -> &[Modification] {
+        self.modification
+            .as_ref()
+            .map_or(&[][..], |tracker| &tracker.found[..])
+    }
+
+    pub fn run(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+    ) -> Result<W, IntcodeError> {
+        loop {
+            match self.resume()? {
+                State::NeedsInput => match input.read() {
+                    Ok(Some(value)) => self.push_input(value),
+                    Ok(None) => return Err(self.fault(ErrorKind::NoInput)),
+                    Err(err) => return Err(self.fault(err.into())),
+                },
+                State::Output(value) => {
+                    if let Err(err) = output.write(value) {
+                        return Err(self.fault(err.into()));
+                    }
+                }
+                State::Halted => return Ok(self.result()),
+            }
+        }
+    }
+
+    //Runs until the program halts, produces an output or waits for an input
+    //that has not been pushed yet. Calling it again continues where it stopped.
+    pub fn resume(&mut self) -> Result<State<W>, IntcodeError> {
+        loop {
+            if let Some(state) = self.step()? {
+                return Ok(state);
+            }
+        }
+    }
+
+    pub fn push_input(&mut self, value: W) {
+        self.input.push_back(value);
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.memory
+    }
+
+    //Writes through this bypass the decode cache, so it is dropped entirely.
+    pub fn memory_mut(&mut self) -> &mut Memory<W> {
+        self.cache.clear();
+        &mut self.memory
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn relative_base(&self) -> W {
+        self.relative_base
+    }
+
+    pub fn pending_input(&self) -> &VecDeque<W> {
+        &self.input
+    }
+
+    pub(crate) fn fault(&self, kind: ErrorKind) -> IntcodeError {
+        IntcodeError {
+            pc: self.pc,
+            instruction: self.memory[self.pc].to_i128(),
+            kind,
+        }
+    }
+
+    fn parameter_index(&self, decoded: &Decoded<W>, offset: usize) -> Result<usize, ErrorKind> {
+        let param = decoded.params[offset - 1];
+        match decoded.modes[offset - 1] {
+            Mode::Position => param.to_address(),
+            Mode::Immediate => self
+                .pc
+                .checked_add(offset)
+                .ok_or(ErrorKind::AddressOutOfBounds),
+            Mode::Relative => self
+                .relative_base
+                .checked_add(param)
+                .ok_or(ErrorKind::AddressOutOfBounds)?
+                .to_address(),
+        }
+    }
+
+    fn target_index(&self, decoded: &Decoded<W>, offset: usize) -> Result<usize, ErrorKind> {
+        if decoded.modes[offset - 1] == Mode::Immediate {
+            return Err(ErrorKind::WriteToImmediate);
+        }
+        self.parameter_index(decoded, offset)
+    }
+
+    fn load(&mut self, address: usize) -> W {
+        let value = self.memory[address];
+        if let Some(tracing) = &mut self.tracing {
+            tracing.operands.push(value);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.count_read(address);
+        }
+        value
+    }
+
+    fn store(&mut self, address: usize, value: W) -> Result<(), ErrorKind> {
+        if let Some(tracker) = &mut self.modification {
+            tracker
+                .writing(self.pc, address)
+                .map_err(ErrorKind::SelfModification)?;
+        }
+        self.memory[address] = value;
+        self.cache.invalidate(address);
+        if let Some(tracing) = &mut self.tracing {
+            tracing.writes.push((address, value));
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.count_write(address);
+        }
+        Ok(())
+    }
+
+    fn execute_instruction(&mut self, ins: Instruction) -> Result<Option<State<W>>, ErrorKind> {
+        let mut state = None;
+        let new_pc = match ins {
+            Instruction::Comparison {
+                kind,
+                target,
+                op1,
+                op2,
+            } => {
+                let (a, b) = (self.load(op1), self.load(op2));
+                let result = match kind {
+                    ComparisonKind::Equals => a == b,
+                    ComparisonKind::LessThan => a < b,
+                };
+                self.store(target, if result { W::ONE } else { W::ZERO })?;
+                self.pc + 4
+            }
+            Instruction::Jump { kind, cond, to } => {
+                let value = self.load(cond);
+                let condition = match kind {
+                    JumpCondition::True => value != W::ZERO,
+                    JumpCondition::False => value == W::ZERO,
+                };
+                if condition {
+                    let to = self.load(to);
+                    to.to_address()?
+                } else {
+                    self.pc + 3
+                }
+            }
+            Instruction::Binary {
+                kind,
+                target,
+                op1,
+                op2,
+            } => {
+                let (a, b) = (self.load(op1), self.load(op2));
+                let res = self
+                    .arithmetic
+                    .apply(kind, a, b)
+                    .ok_or(ErrorKind::Overflow)?;
+                self.store(target, res)?;
+                self.pc + 4
+            }
+            Instruction::Halt => {
+                state = Some(State::Halted);
+                self.pc
+            }
+            Instruction::Input { target } => match self.input.pop_front() {
+                Some(value) => {
+                    self.store(target, value)?;
+                    self.pc + 2
+                }
+                None => return Ok(Some(State::NeedsInput)),
+            },
+            Instruction::Output { target } => {
+                state = Some(State::Output(self.load(target)));
+                self.pc + 2
+            }
+            Instruction::AdjustBase { op } => {
+                let offset = self.load(op);
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or(ErrorKind::Overflow)?;
+                self.pc + 2
+            }
+        };
+        self.pc = new_pc;
+        Ok(state)
+    }
+
+    //Splitting the instruction word is comparatively slow, so the result is
+    //cached until something writes to the instruction.
+    fn decode(&mut self) -> Result<Decoded<W>, ErrorKind> {
+        if let Some(decoded) = self.cache.get(self.pc) {
+            return Ok(decoded);
+        }
+        let (opcode, mode1, mode2, mode3) = split_instruction(self.memory[self.pc])?;
+        let size = match opcode_info(opcode) {
+            Some((_, count, _)) => count + 1,
+            None => return Err(ErrorKind::UnknownOpcode(opcode)),
+        };
+        let mut params = [W::ZERO; 3];
+        for (offset, param) in params.iter_mut().enumerate().take(size - 1) {
+            let address = self
+                .pc
+                .checked_add(offset + 1)
+                .ok_or(ErrorKind::AddressOutOfBounds)?;
+            *param = self.memory[address];
+        }
+        let decoded = Decoded {
+            opcode,
+            modes: [mode1, mode2, mode3],
+            params,
+            size,
+        };
+        self.cache.insert(self.pc, decoded);
+        Ok(decoded)
+    }
+
+    fn parse_instruction(&self, d: &Decoded<W>) -> Result<Instruction, ErrorKind> {
+        Ok(match d.opcode {
+            1 | 2 => Instruction::Binary {
+                kind: if d.opcode == 1 {
+                    BinaryKind::Plus
+                } else {
+                    BinaryKind::Multiply
+                },
+                target: self.target_index(d, 3)?,
+                op1: self.parameter_index(d, 1)?,
+                op2: self.parameter_index(d, 2)?,
+            },
+            3 => Instruction::Input {
+                target: self.target_index(d, 1)?,
+            },
+            4 => Instruction::Output {
+                target: self.parameter_index(d, 1)?,
+            },
+            5 | 6 => Instruction::Jump {
+                kind: if d.opcode == 5 {
+                    JumpCondition::True
+                } else {
+                    JumpCondition::False
+                },
+                cond: self.parameter_index(d, 1)?,
+                to: self.parameter_index(d, 2)?,
+            },
+            7 | 8 => Instruction::Comparison {
+                kind: if d.opcode == 7 {
+                    ComparisonKind::LessThan
+                } else {
+                    ComparisonKind::Equals
+                },
+                target: self.target_index(d, 3)?,
+                op1: self.parameter_index(d, 1)?,
+                op2: self.parameter_index(d, 2)?,
+            },
+            9 => Instruction::AdjustBase {
+                op: self.parameter_index(d, 1)?,
+            },
+            _ => Instruction::Halt,
+        })
+    }
+
+    //Executes a single instruction. Returns the state if it would make resume stop.
+    pub fn step(&mut self) -> Result<Option<State<W>>, IntcodeError> {
+        let decoded = self.decode().map_err(|kind| self.fault(kind))?;
+        let ins = self
+            .parse_instruction(&decoded)
+            .map_err(|kind| self.fault(kind))?;
+        if let Some(tracker) = &mut self.modification {
+            if let Err(modification) = tracker.executing(self.pc, decoded.size) {
+                return Err(self.fault(ErrorKind::SelfModification(modification)));
+            }
+        }
+        if self.tracing.is_none() && self.profile.is_none() {
+            return self
+                .execute_instruction(ins)
+                .map_err(|kind| self.fault(kind));
+        }
+
+        let pc = self.pc;
+        if let Some(tracing) = &mut self.tracing {
+            tracing.operands.clear();
+            tracing.writes.clear();
+        }
+        let state = self
+            .execute_instruction(ins)
+            .map_err(|kind| self.fault(kind))?;
+        if state == Some(State::NeedsInput) {
+            return Ok(state);
+        }
+        if let Some(profile) = &mut self.profile {
+            profile.count_instruction(pc, &decoded);
+            if let Instruction::Jump { .. } = ins {
+                profile.count_jump(pc, self.pc);
+            }
+        }
+        if let Some(tracing) = &mut self.tracing {
+            let record = TraceRecord {
+                pc,
+                instruction: ins,
+                operands: tracing.operands.drain(..).collect(),
+                writes: tracing.writes.drain(..).collect(),
+            };
+            if let Err(err) = tracing.tracer.record(&record) {
+                return Err(IntcodeError {
+                    pc,
+                    instruction: self.memory[pc].to_i128(),
+                    kind: ErrorKind::Io(err),
+                });
+            }
+        }
+        Ok(state)
+    }
+
+    pub fn finished(&self) -> bool {
+        self.memory[self.pc].to_i128() == 99
+    }
+
+    pub fn result(&self) -> W {
+        self.memory[0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn echo() {
+        let mut input = VecDeque::from(vec![42]);
+        let mut output = Vec::new();
+        Computer::new(&[3, 0, 4, 0, 99])
+            .run(&mut input, &mut output)
+            .unwrap();
+        assert_eq!(output, vec![42]);
+    }
+
+    #[test]
+    fn compare_with_eight() {
+        let program = vec![
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for &(value, expected) in &[(7, 999), (8, 1000), (9, 1001)] {
+            let mut input = VecDeque::from(vec![value]);
+            let mut output = Vec::new();
+            Computer::new(&program)
+                .run(&mut input, &mut output)
+                .unwrap();
+            assert_eq!(output, vec![expected]);
+        }
+    }
+
+    #[test]
+    fn resume_yields() {
+        //Adds the two inputs and outputs the sum twice
+        let mut computer = Computer::new(&[3, 13, 3, 14, 1, 13, 14, 15, 4, 15, 4, 15, 99]);
+        assert_eq!(computer.resume().unwrap(), State::NeedsInput);
+        assert_eq!(computer.resume().unwrap(), State::NeedsInput);
+        computer.push_input(20);
+        assert_eq!(computer.resume().unwrap(), State::NeedsInput);
+        computer.push_input(22);
+        assert_eq!(computer.resume().unwrap(), State::Output(42));
+        assert_eq!(computer.resume().unwrap(), State::Output(42));
+        assert_eq!(computer.resume().unwrap(), State::Halted);
+        assert_eq!(computer.resume().unwrap(), State::Halted);
+    }
+
+    #[test]
+    fn relative_mode() {
+        let program = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let mut output = Vec::new();
+        Computer::new(&program)
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, program);
+    }
+
+    #[test]
+    fn relative_write() {
+        //Moves the base to 10 and stores the input at 10 + 2
+        let mut computer = Computer::new(&[109, 10, 203, 2, 204, 2, 99]);
+        let mut input = VecDeque::from(vec![77]);
+        let mut output = Vec::new();
+        computer.run(&mut input, &mut output).unwrap();
+        assert_eq!(output, vec![77]);
+        assert_eq!(computer.memory()[12], 77);
+    }
+
+    #[test]
+    fn large_numbers_and_far_addresses() {
+        let mut output = Vec::new();
+        Computer::new(&[104, 1125899906842624, 99])
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![1125899906842624]);
+
+        //Writes past the end of the program and reads the value back
+        let mut computer = Computer::new(&[1101, 3, 4, 1000000000000, 4, 1000000000000, 99]);
+        let mut output = Vec::new();
+        computer.run(&mut VecDeque::new(), &mut output).unwrap();
+        assert_eq!(output, vec![7]);
+    }
+
+    #[test]
+    fn faults() {
+        let mut input = VecDeque::new();
+        let mut output = Vec::new();
+        let err = Computer::new(&[1, 0, 0, 0, 42])
+            .run(&mut input, &mut output)
+            .unwrap_err();
+        assert_eq!((err.pc, err.instruction), (4, 42));
+        assert!(matches!(err.kind, ErrorKind::UnknownOpcode(42)));
+
+        let err = Computer::new(&[301, 0, 0, 0, 99])
+            .run(&mut input, &mut output)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::BadMode(3)));
+
+        let err = Computer::new(&[1, -1, 0, 0, 99])
+            .run(&mut input, &mut output)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::NegativeAddress(-1)));
+
+        let err = Computer::new(&[10001, 0, 0, 0, 99])
+            .run(&mut input, &mut output)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::WriteToImmediate));
+
+        let err = Computer::new(&[3, 0, 99])
+            .run(&mut input, &mut output)
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::NoInput));
+    }
+
+    #[test]
+    fn arithmetic_policies() {
+        //MUL [5], [5], [0] on i64::MAX
+        let program = [2, 5, 5, 0, 99, i64::MAX];
+        let err = Computer::new(&program)
+            .run(&mut VecDeque::new(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(err.pc, 0);
+        assert!(matches!(err.kind, ErrorKind::Overflow));
+
+        let mut computer = Computer::new(&program);
+        computer.set_arithmetic(Arithmetic::Saturating);
+        let result = computer.run(&mut VecDeque::new(), &mut Vec::new());
+        assert_eq!(result.unwrap(), i64::MAX);
+
+        let mut computer = Computer::new(&program);
+        computer.set_arithmetic(Arithmetic::Wrapping);
+        let result = computer.run(&mut VecDeque::new(), &mut Vec::new());
+        assert_eq!(result.unwrap(), 1);
+    }
+
+    #[test]
+    fn word_types() {
+        //MUL [5], [5], [0] overflows i64 but not i128
+        let square = |value: i128| [2, 5, 5, 0, 99, value];
+        let mut computer = Computer::<i128>::from_program(&square(i64::MAX as i128));
+        let result = computer.run(&mut VecDeque::new(), &mut Vec::new());
+        assert_eq!(result.unwrap(), (i64::MAX as i128) * (i64::MAX as i128));
+
+        let mut output = Vec::new();
+        Computer::<i32>::from_program(&[3, 0, 4, 0, 99])
+            .run(&mut VecDeque::from(vec![-7]), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![-7i32]);
+
+        let err = Computer::<i32>::from_program(&[2, 5, 5, 0, 99, 1 << 16])
+            .run(&mut VecDeque::new(), &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::Overflow));
+    }
+
+    #[test]
+    fn self_modification() {
+        //ADD #99, #0, [4] turns the following word into HALT
+        let program = [1101, 99, 0, 4, 0];
+        let mut computer = Computer::new(&program);
+        computer.track_self_modification(Policy::Report);
+        computer.run(&mut VecDeque::new(), &mut Vec::new()).unwrap();
+        assert_eq!(
+            computer.modifications(),
+            &[Modification::Execute {
+                address: 4,
+                pc: 4,
+                writer: 0
+            }]
+        );
+
+        //Loop that rewrites the first parameter of its own ADD
+        let program = [1101, 7, 0, 1, 1105, 1, 0];
+        let mut computer = Computer::new(&program);
+        computer.track_self_modification(Policy::Report);
+        computer.step().unwrap();
+        assert_eq!(
+            computer.modifications(),
+            &[Modification::Overwrite { address: 1, pc: 0 }]
+        );
+        computer.step().unwrap();
+        computer.step().unwrap();
+        assert_eq!(
+            computer.modifications()[1..],
+            [
+                Modification::Execute {
+                    address: 1,
+                    pc: 0,
+                    writer: 0
+                },
+                Modification::Overwrite { address: 1, pc: 0 }
+            ]
+        );
+
+        let mut computer = Computer::new(&program);
+        computer.track_self_modification(Policy::Reject);
+        let err = computer.step().unwrap_err();
+        assert!(matches!(
+            err.kind,
+            ErrorKind::SelfModification(Modification::Overwrite { address: 1, pc: 0 })
+        ));
+        assert_eq!(computer.memory()[1], 7);
+
+        //Forks keep the policy
+        let mut fork = Computer::new(&program);
+        fork.track_self_modification(Policy::Reject);
+        let mut fork = fork.clone();
+        let err = fork.step().unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::SelfModification(_)));
+    }
+
+    #[test]
+    fn bad_input() {
+        let mut input = crate::device::LineReader::new("seven\n".as_bytes());
+        let err = Computer::new(&[3, 0, 99])
+            .run(&mut input, &mut Vec::new())
+            .unwrap_err();
+        assert!(matches!(err.kind, ErrorKind::BadInput(_)));
+    }
+
+    #[test]
+    fn forks_branch_independently() {
+        let mut computer = Computer::new(&[3, 9, 1002, 9, 2, 9, 4, 9, 99, 0]);
+        assert_eq!(computer.resume().unwrap(), State::NeedsInput);
+        let outputs = (1..=3)
+            .map(|value| {
+                let mut fork = computer.clone();
+                fork.push_input(value);
+                fork.resume().unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            outputs,
+            vec![State::Output(2), State::Output(4), State::Output(6)]
+        );
+        assert_eq!(computer.memory()[9], 0);
+        assert_eq!(computer.pc(), 0);
+    }
+
+    #[test]
+    fn rewritten_code_is_decoded_again() {
+        //OUT #1, then ADD #5, #0, [1] turns it into OUT #5 and loops once
+        let mut program = vec![104, 1, 1101, 5, 0, 1, 1001, 20, -1, 20, 1005, 20, 0, 99];
+        program.resize(21, 0);
+        program[20] = 2;
+        let mut output = Vec::new();
+        Computer::new(&program)
+            .run(&mut VecDeque::new(), &mut output)
+            .unwrap();
+        assert_eq!(output, vec![1, 5]);
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::computer::{Computer, State};
+use crate::disasm::{disassemble_memory, Line};
+use crate::error::IntcodeError;
+use crate::limits::{Budget, Limits, Reason};
+
+//Why the debugger handed control back.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Stop {
+    Stepped,
+    Breakpoint(usize),
+    Watchpoint { address: usize, old: i64, new: i64 },
+    NeedsInput,
+    Halted,
+    //Continuing ran out of its limits, it can simply be continued again
+    Limit(Reason),
+}
+
+pub struct Debugger {
+    computer: Computer,
+    breakpoints: BTreeSet<usize>,
+    //Last seen value of every watched address
+    watchpoints: BTreeMap<usize, i64>,
+    output: Vec<i64>,
+    limits: Limits,
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Self {
+        Debugger {
+            computer,
+            breakpoints: BTreeSet::new(),
+            watchpoints: BTreeMap::new(),
+            output: Vec::new(),
+            limits: Limits::default(),
+        }
+    }
+
+    pub fn computer(&self) -> &Computer {
+        &self.computer
+    }
+
+    pub fn computer_mut(&mut self) -> &mut Computer {
+        &mut self.computer
+    }
+
+    pub fn breakpoints(&self) -> impl Iterator<Item = &usize> {
+        self.breakpoints.iter()
+    }
+
+    pub fn watchpoints(&self) -> impl Iterator<Item = &usize> {
+        self.watchpoints.keys()
+    }
+
+    //Returns false if there already was a breakpoint at address.
+    pub fn add_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.insert(address)
+    }
+
+    pub fn remove_breakpoint(&mut self, address: usize) -> bool {
+        self.breakpoints.remove(&address)
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) -> bool {
+        let value = self.computer.memory()[address];
+        self.watchpoints.insert(address, value).is_none()
+    }
+
+    pub fn remove_watchpoint(&mut self, address: usize) -> bool {
+        self.watchpoints.remove(&address).is_some()
+    }
+
+    //Bounds every cont and step_over, so a program stuck in a loop hands
+    //control back.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    //Outputs produced since the last call.
+    pub fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.output)
+    }
+
+    //Executes exactly one instruction.
+    pub fn step(&mut self) -> Result<Stop, IntcodeError> {
+        Ok(self.step_once()?.unwrap_or(Stop::Stepped))
+    }
+
+    //Runs until the instruction after the current one is reached. Jumps that
+    //come back to it, like the end of a loop, are followed.
+    pub fn step_over(&mut self) -> Result<Stop, IntcodeError> {
+        let pc = self.computer.pc();
+        let next = pc + self.listing(0, 1)[0].size();
+        let temporary = self.add_breakpoint(next);
+        let stop = self.cont();
+        if temporary {
+            self.remove_breakpoint(next);
+        }
+        match stop {
+            Ok(Stop::Breakpoint(address)) if address == next && temporary => Ok(Stop::Stepped),
+            stop => stop,
+        }
+    }
+
+    //Runs until a breakpoint or watchpoint triggers, input is missing or the
+    //program halts or the limits are reached. A breakpoint at the current pc
+    //does not stop it.
+    pub fn cont(&mut self) -> Result<Stop, IntcodeError> {
+        let mut budget = Budget::new(&self.limits);
+        loop {
+            if let Some(reason) = budget.exhausted() {
+                return Ok(Stop::Limit(reason));
+            }
+            if let Some(stop) = self.step_once()? {
+                return Ok(stop);
+            }
+            if let Some(reason) = budget.count(&self.computer) {
+                return Ok(Stop::Limit(reason));
+            }
+            let pc = self.computer.pc();
+            if self.breakpoints.contains(&pc) {
+                return Ok(Stop::Breakpoint(pc));
+            }
+        }
+    }
+
+    //Lines of disassembly around pc, always including a line at pc.
+    pub fn listing(&self, before: usize, after: usize) -> Vec<Line> {
+        let pc = self.computer.pc();
+        let start = pc.saturating_sub(before);
+        let lines = disassemble_memory(self.computer.memory(), start, pc, pc - start + after);
+        let mut listing = Vec::new();
+        let mut after_pc = 0;
+        for line in lines {
+            if line.address() >= pc {
+                if after_pc == after {
+                    break;
+                }
+                after_pc += 1;
+            }
+            listing.push(line);
+        }
+        listing
+    }
+
+    fn step_once(&mut self) -> Result<Option<Stop>, IntcodeError> {
+        let state = self.computer.step()?;
+        //Outputs are kept even if a watchpoint stops the step
+        if let Some(State::Output(value)) = state {
+            self.output.push(value);
+        }
+        for (&address, last) in self.watchpoints.iter_mut() {
+            let value = self.computer.memory()[address];
+            if value != *last {
+                let old = *last;
+                *last = value;
+                return Ok(Some(Stop::Watchpoint {
+                    address,
+                    old,
+                    new: value,
+                }));
+            }
+        }
+        Ok(match state {
+            Some(State::NeedsInput) => Some(Stop::NeedsInput),
+            Some(State::Halted) => Some(Stop::Halted),
+            Some(State::Output(_)) | None => None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures;
+
+    fn countdown() -> Debugger {
+        Debugger::new(Computer::new(&fixtures::countdown()))
+    }
+
+    #[test]
+    fn breakpoints() {
+        let mut debugger = countdown();
+        debugger.add_breakpoint(6);
+        assert_eq!(debugger.cont().unwrap(), Stop::Breakpoint(6));
+        assert_eq!(debugger.take_output(), vec![3]);
+        assert_eq!(debugger.cont().unwrap(), Stop::Breakpoint(6));
+        debugger.remove_breakpoint(6);
+        assert_eq!(debugger.cont().unwrap(), Stop::Halted);
+        assert_eq!(debugger.take_output(), vec![2, 1]);
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut debugger = countdown();
+        debugger.add_watchpoint(20);
+        assert_eq!(
+            debugger.cont().unwrap(),
+            Stop::Watchpoint {
+                address: 20,
+                old: 3,
+                new: 2
+            }
+        );
+        assert_eq!(debugger.computer().pc(), 6);
+
+        //A value set by hand is reported on the next step, the output of that
+        //step is kept
+        let mut debugger = countdown();
+        debugger.add_watchpoint(20);
+        debugger.computer_mut().memory_mut()[20] = 5;
+        assert!(matches!(
+            debugger.step().unwrap(),
+            Stop::Watchpoint { old: 3, new: 5, .. }
+        ));
+        assert_eq!(debugger.take_output(), vec![5]);
+    }
+
+    #[test]
+    fn endless_loops_stop() {
+        //JT #1, #0 jumps to itself
+        let mut debugger = Debugger::new(Computer::new(&[1105, 1, 0]));
+        debugger.set_limits(Limits {
+            detect_cycles: true,
+            ..Limits::default()
+        });
+        assert_eq!(
+            debugger.step_over().unwrap(),
+            Stop::Limit(Reason::Cycle { length: 1 })
+        );
+        debugger.set_limits(Limits {
+            instructions: Some(100),
+            ..Limits::default()
+        });
+        assert_eq!(debugger.cont().unwrap(), Stop::Limit(Reason::Instructions));
+    }
+
+    #[test]
+    fn stepping() {
+        let mut debugger = countdown();
+        assert_eq!(debugger.step().unwrap(), Stop::Stepped);
+        assert_eq!(debugger.computer().pc(), 2);
+        assert_eq!(debugger.step().unwrap(), Stop::Stepped);
+        //Stepping over the loop jump runs the whole loop
+        assert_eq!(debugger.step_over().unwrap(), Stop::Stepped);
+        assert_eq!(debugger.computer().pc(), 9);
+        assert_eq!(debugger.take_output(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn listing_around_pc() {
+        let mut debugger = countdown();
+        debugger.step().unwrap();
+        debugger.step().unwrap();
+        let listing = debugger
+            .listing(4, 2)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            vec![
+                "0002: ADD [20], #-1, [20]",
+                "0006: JT [20], #0",
+                "0009: HALT"
+            ]
+        );
+    }
+}
@@ -0,0 +1,201 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::word::Word;
+
+//Source of values for the Input instruction. None means no value is available,
+//values that can not be parsed are reported as InvalidData.
+pub trait Input<W = i64> {
+    fn read(&mut self) -> io::Result<Option<W>>;
+}
+
+//Sink for the values produced by the Output instruction.
+pub trait Output<W = i64> {
+    fn write(&mut self, value: W) -> io::Result<()>;
+}
+
+fn parse_value<W: Word>(text: &str) -> io::Result<W> {
+    W::parse(text).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{:?} is no number", text),
+        )
+    })
+}
+
+//Interactive input from the terminal with a ">" prompt.
+pub struct Stdin;
+
+impl<W: Word> Input<W> for Stdin {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        print!(">");
+        io::stdout().flush()?;
+        let mut buf = String::new();
+        if io::stdin().read_line(&mut buf)? == 0 {
+            return Ok(None);
+        }
+        parse_value(buf.trim()).map(Some)
+    }
+}
+
+pub struct Stdout;
+
+impl<W: Word> Output<W> for Stdout {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        writeln!(io::stdout(), "{}", value)
+    }
+}
+
+impl<W> Input<W> for VecDeque<W> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok(self.pop_front())
+    }
+}
+
+impl<W> Output<W> for VecDeque<W> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl<W> Output<W> for Vec<W> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+pub struct InputFn<F>(pub F);
+
+impl<W, F: FnMut() -> Option<W>> Input<W> for InputFn<F> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        Ok((self.0)())
+    }
+}
+
+pub struct OutputFn<F>(pub F);
+
+impl<W, F: FnMut(W)> Output<W> for OutputFn<F> {
+    fn write(&mut self, value: W) -> io::Result<()> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+//Reads one number per line, blank lines are skipped.
+pub struct LineReader<R> {
+    reader: R,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        LineReader { reader }
+    }
+}
+
+impl LineReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(LineReader::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead, W: Word> Input<W> for LineReader<R> {
+    fn read(&mut self) -> io::Result<Option<W>> {
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            if self.reader.read_line(&mut buf)? == 0 {
+                return Ok(None);
+            }
+            let line = buf.trim();
+            if !line.is_empty() {
+                return parse_value(line).map(Some);
+            }
+        }
+    }
+}
+
+//Writes one number per line.
+pub struct LineWriter<W> {
+    writer: W,
+}
+
+impl<W: Write> LineWriter<W> {
+    pub fn new(writer: W) -> Self {
+        LineWriter { writer }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl LineWriter<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(LineWriter::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write, V: Word> Output<V> for LineWriter<W> {
+    fn write(&mut self, value: V) -> io::Result<()> {
+        writeln!(self.writer, "{}", value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Input, InputFn, LineReader, LineWriter, Output, OutputFn};
+    use std::collections::VecDeque;
+
+    #[test]
+    fn queue_roundtrip() {
+        let mut queue = VecDeque::new();
+        queue.write(3).unwrap();
+        queue.write(-4).unwrap();
+        assert_eq!(queue.read().unwrap(), Some(3));
+        assert_eq!(queue.read().unwrap(), Some(-4));
+        assert_eq!(queue.read().unwrap(), None);
+    }
+
+    #[test]
+    fn line_reader_skips_blank_lines() {
+        let mut reader = LineReader::new("1\n\n  -7 \n".as_bytes());
+        let mut read = || Input::<i64>::read(&mut reader).unwrap();
+        assert_eq!(read(), Some(1));
+        assert_eq!(read(), Some(-7));
+        assert_eq!(read(), None);
+    }
+
+    #[test]
+    fn line_reader_rejects_garbage() {
+        let mut reader = LineReader::new("x1\n".as_bytes());
+        let err = Input::<i64>::read(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn line_writer() {
+        let mut writer = LineWriter::new(Vec::new());
+        writer.write(5).unwrap();
+        writer.write(-1).unwrap();
+        assert_eq!(writer.into_inner(), b"5\n-1\n");
+    }
+
+    #[test]
+    fn closures() {
+        let mut next = 0;
+        let mut input = InputFn(|| {
+            next += 1;
+            Some(next)
+        });
+        assert_eq!(input.read().unwrap(), Some(1));
+        assert_eq!(input.read().unwrap(), Some(2));
+
+        let mut seen = Vec::new();
+        OutputFn(|v| seen.push(v)).write(9).unwrap();
+        assert_eq!(seen, vec![9]);
+    }
+}
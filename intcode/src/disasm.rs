@@ -0,0 +1,239 @@
+use std::fmt;
+
+use crate::computer::{opcode_info, split_instruction, Mode};
+use crate::memory::Memory;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Line {
+    Instruction {
+        address: usize,
+        //The instruction word as it is in memory, it may have redundant mode
+        //digits that the interpreter ignores.
+        word: i64,
+        mnemonic: &'static str,
+        operands: Vec<Operand>,
+    },
+    Data {
+        address: usize,
+        value: i64,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Instruction { address, .. } | Line::Data { address, .. } => *address,
+        }
+    }
+
+    //Number of memory words the line covers.
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Instruction { operands, .. } => operands.len() + 1,
+            Line::Data { .. } => 1,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Position(value) => write!(f, "[{}]", value),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Relative(value) if *value < 0 => write!(f, "rb{}", value),
+            Operand::Relative(value) => write!(f, "rb+{}", value),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}: ", self.address())?;
+        match self {
+            Line::Instruction {
+                word,
+                mnemonic,
+                operands,
+                ..
+            } => {
+                write!(f, "{}", mnemonic)?;
+                //Redundant mode digits are shown so the listing assembles
+                //back to the same word
+                if *word != canonical(word % 100, operands) {
+                    write!(f, "({})", word)?;
+                }
+                for (i, operand) in operands.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { " " } else { ", " }, operand)?;
+                }
+                Ok(())
+            }
+            Line::Data { value, .. } => write!(f, "DATA {}", value),
+        }
+    }
+}
+
+//Instruction word the assembler emits for opcode and operands.
+fn canonical(opcode: i64, operands: &[Operand]) -> i64 {
+    let mut word = opcode;
+    let mut scale = 100;
+    for operand in operands {
+        let mode = match operand {
+            Operand::Position(_) => Mode::Position,
+            Operand::Immediate(_) => Mode::Immediate,
+            Operand::Relative(_) => Mode::Relative,
+        };
+        word += scale * mode as i64;
+        scale *= 10;
+    }
+    word
+}
+
+//Decodes the instruction at address the way the interpreter does, mode digits
+//of unused parameters are ignored. Words the interpreter would fault on, like
+//a write through an immediate parameter, are data.
+pub fn decode(image: &[i64], address: usize) -> Option<Line> {
+    let word = *image.get(address)?;
+    let (opcode, mode1, mode2, mode3) = split_instruction(word).ok()?;
+    let (mnemonic, count, target) = opcode_info(opcode)?;
+    let params = image.get(address + 1..address + 1 + count)?;
+
+    let mut operands = Vec::with_capacity(count);
+    for (i, (&mode, &value)) in [mode1, mode2, mode3].iter().zip(params).enumerate() {
+        if mode == Mode::Immediate && target == Some(i) {
+            return None;
+        }
+        operands.push(match mode {
+            Mode::Position => Operand::Position(value),
+            Mode::Immediate => Operand::Immediate(value),
+            Mode::Relative => Operand::Relative(value),
+        });
+    }
+    Some(Line::Instruction {
+        address,
+        word,
+        mnemonic,
+        operands,
+    })
+}
+
+//Linear sweep over the whole image.
+pub fn disassemble(image: &[i64]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = 0;
+    while address < image.len() {
+        let line = decode(image, address).unwrap_or(Line::Data {
+            address,
+            value: image[address],
+        });
+        address += line.size();
+        lines.push(line);
+    }
+    lines
+}
+
+//Disassembles count lines of live memory starting at start. Lines that would
+//overlap align are cut into data, so the listing always has a line at align.
+pub fn disassemble_memory(memory: &Memory, start: usize, align: usize, count: usize) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut address = start;
+    while lines.len() < count {
+        let window = (address..address + 4)
+            .map(|address| memory[address])
+            .collect::<Vec<_>>();
+        let line = match decode(&window, 0) {
+            Some(Line::Instruction {
+                word,
+                mnemonic,
+                operands,
+                ..
+            }) if address >= align || address + operands.len() < align => Line::Instruction {
+                address,
+                word,
+                mnemonic,
+                operands,
+            },
+            _ => Line::Data {
+                address,
+                value: window[0],
+            },
+        };
+        address += line.size();
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn listing() {
+        let image = [1002, 4, 3, 4, 33, 21101, -2, 7, 3, 204, -1, 1099, 99];
+        let listing = disassemble(&image)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            vec![
+                "0000: MUL [4], #3, [4]",
+                "0004: DATA 33",
+                "0005: ADD #-2, #7, rb+3",
+                "0009: OUT rb-1",
+                "0011: HALT(1099)",
+                "0012: HALT",
+            ]
+        );
+    }
+
+    #[test]
+    fn undecodable_words_are_data() {
+        //Immediate write target, unknown opcode and a truncated instruction
+        let lines = disassemble(&[11101, 42, 1, 0]);
+        assert_eq!(lines.len(), 4);
+        assert!(lines.iter().all(|line| matches!(line, Line::Data { .. })));
+    }
+
+    #[test]
+    fn redundant_mode_digits() {
+        let listing = disassemble(&[1104, 7, 10099, 100001, 1, 2, 3])
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            vec![
+                "0000: OUT(1104) #7",
+                "0002: HALT(10099)",
+                "0003: ADD(100001) [1], [2], [3]"
+            ]
+        );
+    }
+
+    #[test]
+    fn memory_listing_aligns() {
+        let memory = Memory::from(&[1101, 1, 2, 3, 99][..]);
+        let listing = disassemble_memory(&memory, 0, 2, 3)
+            .iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            listing,
+            vec![
+                "0000: DATA 1101",
+                "0001: DATA 1",
+                "0002: MUL [3], [99], [0]"
+            ]
+        );
+        let line = disassemble_memory(&memory, 0, 0, 1).remove(0);
+        assert_eq!(line.to_string(), "0000: ADD #1, #2, [3]");
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use crate::modification::Modification;
+
+//A fault raised while executing the instruction at pc.
+#[derive(Debug)]
+pub struct IntcodeError {
+    pub pc: usize,
+    pub instruction: i128,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    UnknownOpcode(i64),
+    BadMode(i64),
+    NegativeAddress(i128),
+    AddressOutOfBounds,
+    WriteToImmediate,
+    Overflow,
+    SelfModification(Modification),
+    NoInput,
+    BadInput(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "fault at pc {} (instruction {}): {}",
+            self.pc, self.instruction, self.kind
+        )
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::UnknownOpcode(opcode) => write!(f, "unknown opcode {}", opcode),
+            ErrorKind::BadMode(mode) => write!(f, "unknown parameter mode {}", mode),
+            ErrorKind::NegativeAddress(address) => write!(f, "negative address {}", address),
+            ErrorKind::AddressOutOfBounds => write!(f, "address out of bounds"),
+            ErrorKind::WriteToImmediate => write!(f, "write to an immediate parameter"),
+            ErrorKind::Overflow => write!(f, "arithmetic overflow"),
+            ErrorKind::SelfModification(modification) => {
+                write!(f, "self modifying code: {}", modification)
+            }
+            ErrorKind::NoInput => write!(f, "no input available"),
+            ErrorKind::BadInput(msg) => write!(f, "bad input: {}", msg),
+            ErrorKind::Io(err) => write!(f, "i/o failure: {}", err),
+        }
+    }
+}
+
+impl Error for IntcodeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ErrorKind::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+//Devices report unparsable values as InvalidData, everything else is an i/o failure.
+impl From<io::Error> for ErrorKind {
+    fn from(err: io::Error) -> Self {
+        if err.kind() == io::ErrorKind::InvalidData {
+            ErrorKind::BadInput(err.to_string())
+        } else {
+            ErrorKind::Io(err)
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//Programs used by the tests of several modules.
+
+//Outputs [20] and counts it down from 3, halts once it is 0.
+pub(crate) fn countdown() -> Vec<i64> {
+    let mut program = vec![4, 20, 1001, 20, -1, 20, 1005, 20, 0, 99];
+    program.resize(21, 0);
+    program[20] = 3;
+    program
+}
@@ -0,0 +1,32 @@
+pub mod amplifier;
+pub mod ascii;
+pub mod asm;
+mod cache;
+pub mod cfg;
+pub mod computer;
+pub mod debugger;
+pub mod device;
+pub mod disasm;
+pub mod error;
+#[cfg(test)]
+mod fixtures;
+pub mod limits;
+pub mod loader;
+pub mod memory;
+pub mod modification;
+pub mod network;
+pub mod profile;
+pub mod snapshot;
+pub mod solver;
+pub mod trace;
+pub mod word;
+
+pub use crate::computer::{Arithmetic, Computer, State};
+pub use crate::error::{ErrorKind, IntcodeError};
+pub use crate::loader::LoadError;
+pub use crate::memory::Memory;
+pub use crate::word::Word;
+
+pub fn parse_program(input: &str) -> Result<Vec<i64>, LoadError> {
+    loader::parse(input)
+}
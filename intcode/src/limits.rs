@@ -0,0 +1,265 @@
+use std::time::{Duration, Instant};
+
+use crate::computer::{Computer, State};
+use crate::device::{Input, Output};
+use crate::error::{ErrorKind, IntcodeError};
+use crate::snapshot::Snapshot;
+use crate::word::Word;
+
+//The clock is only read every this many instructions.
+const CLOCK_INTERVAL: u64 = 1024;
+
+//Bounds for run_limited. Everything is off by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub instructions: Option<u64>,
+    pub time: Option<Duration>,
+    //Stop when the machine reaches a state it was in before without reading
+    //input in between. It would loop forever from there.
+    pub detect_cycles: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reason {
+    Instructions,
+    Time,
+    Cycle { length: u64 },
+}
+
+pub enum Outcome<W = i64> {
+    Halted(W),
+    //The machine can be resumed from the snapshot, e.g. with a larger budget
+    Stopped {
+        reason: Reason,
+        steps: u64,
+        snapshot: Box<Snapshot<W>>,
+    },
+}
+
+//Brent's algorithm: compare every state with a checkpoint that is moved
+//forward in doubling distances. Clones share memory pages, so checkpoints and
+//comparisons are cheap.
+struct CycleDetector<W> {
+    checkpoint: Option<Computer<W>>,
+    at: u64,
+    power: u64,
+}
+
+impl<W: Word> CycleDetector<W> {
+    fn new() -> Self {
+        CycleDetector {
+            checkpoint: None,
+            at: 0,
+            power: 1,
+        }
+    }
+
+    fn reset(&mut self) {
+        *self = CycleDetector::new();
+    }
+
+    //Returns the cycle length once the state repeats.
+    fn check(&mut self, computer: &Computer<W>, steps: u64) -> Option<u64> {
+        if let Some(checkpoint) = &self.checkpoint {
+            if checkpoint.pc() == computer.pc()
+                && checkpoint.relative_base() == computer.relative_base()
+                && checkpoint.pending_input() == computer.pending_input()
+                && checkpoint.memory() == computer.memory()
+            {
+                return Some(steps - self.at);
+            }
+        }
+        if self.checkpoint.is_none() || steps - self.at >= self.power {
+            self.checkpoint = Some(computer.clone());
+            self.at = steps;
+            self.power *= 2;
+        }
+        None
+    }
+}
+
+//Keeps count of a run against its limits.
+pub(crate) struct Budget<W> {
+    limits: Limits,
+    start: Instant,
+    steps: u64,
+    cycles: CycleDetector<W>,
+}
+
+impl<W: Word> Budget<W> {
+    pub fn new(limits: &Limits) -> Self {
+        Budget {
+            limits: *limits,
+            start: Instant::now(),
+            steps: 0,
+            cycles: CycleDetector::new(),
+        }
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    //Checked before every instruction.
+    pub fn exhausted(&self) -> Option<Reason> {
+        if self
+            .limits
+            .instructions
+            .is_some_and(|limit| self.steps >= limit)
+        {
+            Some(Reason::Instructions)
+        } else if self.steps.is_multiple_of(CLOCK_INTERVAL)
+            && self
+                .limits
+                .time
+                .is_some_and(|time| self.start.elapsed() >= time)
+        {
+            Some(Reason::Time)
+        } else {
+            None
+        }
+    }
+
+    //Counts an executed instruction, Some if the machine is in a cycle now.
+    pub fn count(&mut self, computer: &Computer<W>) -> Option<Reason> {
+        self.steps += 1;
+        if !self.limits.detect_cycles {
+            return None;
+        }
+        let length = self.cycles.check(computer, self.steps)?;
+        Some(Reason::Cycle { length })
+    }
+
+    //Input changes the state from outside, so earlier states say nothing.
+    pub fn input_read(&mut self) {
+        self.cycles.reset();
+    }
+}
+
+impl<W: Word> Computer<W> {
+    //Like run, but gives up when a limit is reached. Instructions waiting for
+    //input are not counted until they run.
+    pub fn run_limited(
+        &mut self,
+        input: &mut dyn Input<W>,
+        output: &mut dyn Output<W>,
+        limits: &Limits,
+    ) -> Result<Outcome<W>, IntcodeError> {
+        let mut budget = Budget::new(limits);
+        loop {
+            if let Some(reason) = budget.exhausted() {
+                return Ok(self.stopped(reason, budget.steps()));
+            }
+
+            match self.step()? {
+                Some(State::NeedsInput) => {
+                    match input.read() {
+                        Ok(Some(value)) => self.push_input(value),
+                        Ok(None) => return Err(self.fault(ErrorKind::NoInput)),
+                        Err(err) => return Err(self.fault(err.into())),
+                    }
+                    budget.input_read();
+                    continue;
+                }
+                Some(State::Output(value)) => {
+                    if let Err(err) = output.write(value) {
+                        return Err(self.fault(err.into()));
+                    }
+                }
+                Some(State::Halted) => return Ok(Outcome::Halted(self.result())),
+                None => {}
+            }
+            if let Some(reason) = budget.count(self) {
+                return Ok(self.stopped(reason, budget.steps()));
+            }
+        }
+    }
+
+    fn stopped(&self, reason: Reason, steps: u64) -> Outcome<W> {
+        Outcome::Stopped {
+            reason,
+            steps,
+            snapshot: Box::new(Snapshot::new(self.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+
+    fn run(program: &[i64], limits: Limits) -> Outcome {
+        Computer::new(program)
+            .run_limited(&mut VecDeque::new(), &mut Vec::new(), &limits)
+            .unwrap()
+    }
+
+    #[test]
+    fn instruction_budget() {
+        //Counts [7] up forever
+        let limits = Limits {
+            instructions: Some(10),
+            ..Limits::default()
+        };
+        match run(&[1001, 7, 1, 7, 1105, 1, 0, 0], limits) {
+            Outcome::Stopped {
+                reason: Reason::Instructions,
+                steps: 10,
+                snapshot,
+            } => {
+                assert_eq!(snapshot.computer.memory()[7], 5);
+                assert_eq!(snapshot.computer.pc(), 0);
+            }
+            _ => panic!("budget was not enforced"),
+        }
+        assert!(matches!(run(&[99], limits), Outcome::Halted(99)));
+    }
+
+    #[test]
+    fn time_budget() {
+        let limits = Limits {
+            time: Some(Duration::from_millis(1)),
+            ..Limits::default()
+        };
+        let outcome = run(&[1001, 7, 1, 7, 1105, 1, 0, 0], limits);
+        assert!(matches!(
+            outcome,
+            Outcome::Stopped {
+                reason: Reason::Time,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn cycles() {
+        let limits = Limits {
+            detect_cycles: true,
+            ..Limits::default()
+        };
+        //Toggles [7] between 0 and 1 forever
+        let outcome = run(&[1008, 7, 0, 7, 1105, 1, 0, 0], limits);
+        assert!(matches!(
+            outcome,
+            Outcome::Stopped {
+                reason: Reason::Cycle { length: 4 },
+                ..
+            }
+        ));
+        //A counter never repeats, so only the budget stops it
+        let limits = Limits {
+            instructions: Some(10_000),
+            detect_cycles: true,
+            ..Limits::default()
+        };
+        let outcome = run(&[1001, 7, 1, 7, 1105, 1, 0, 0], limits);
+        assert!(matches!(
+            outcome,
+            Outcome::Stopped {
+                reason: Reason::Instructions,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,201 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::path::Path;
+
+use crate::word::Word;
+
+//Header of binary images, followed by the version, the word size in bytes and
+//the little-endian words.
+const MAGIC: &[u8; 4] = b"ICIM";
+const VERSION: u8 = 1;
+
+//A token of a text program that is no valid word. Line and column start at 1,
+//index is the position of the token in the program, which is its address.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct LoadError {
+    pub line: usize,
+    pub column: usize,
+    pub index: usize,
+    pub token: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.token.is_empty() {
+            write!(
+                f,
+                "{}:{}: value {} is missing",
+                self.line, self.column, self.index
+            )
+        } else {
+            write!(
+                f,
+                "{}:{}: value {} {:?} is no valid number",
+                self.line, self.column, self.index, self.token
+            )
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+//Values are separated by commas, whitespace or both. Everything after a # is
+//a comment. A single trailing comma is fine, an empty value between two commas
+//is not.
+pub fn parse<W: Word>(text: &str) -> Result<Vec<W>, LoadError> {
+    let mut words = Vec::new();
+    //Position of the last comma if no value followed it yet
+    let mut open_comma: Option<(usize, usize)> = None;
+    let mut expect_comma = false;
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap();
+        let mut chars = line.char_indices().peekable();
+        while let Some((start, c)) = chars.next() {
+            let position = (line_index + 1, line[..start].chars().count() + 1);
+            if c == ',' {
+                if open_comma.is_some() || !expect_comma {
+                    return Err(missing(position, words.len()));
+                }
+                open_comma = Some(position);
+                expect_comma = false;
+            } else if !c.is_whitespace() {
+                let mut end = start + c.len_utf8();
+                while let Some(&(next, c)) = chars.peek() {
+                    if c == ',' || c.is_whitespace() {
+                        break;
+                    }
+                    end = next + c.len_utf8();
+                    chars.next();
+                }
+                let token = &line[start..end];
+                match W::parse(token) {
+                    Ok(word) => words.push(word),
+                    Err(_) => {
+                        return Err(LoadError {
+                            line: position.0,
+                            column: position.1,
+                            index: words.len(),
+                            token: token.to_string(),
+                        })
+                    }
+                }
+                open_comma = None;
+                expect_comma = true;
+            }
+        }
+    }
+    Ok(words)
+}
+
+fn missing((line, column): (usize, usize), index: usize) -> LoadError {
+    LoadError {
+        line,
+        column,
+        index,
+        token: String::new(),
+    }
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub fn write_binary<W: Word, T: Write>(image: &[W], mut writer: T) -> io::Result<()> {
+    let size = mem::size_of::<W>();
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[VERSION, size as u8])?;
+    for word in image {
+        writer.write_all(&word.to_i128().to_le_bytes()[..size])?;
+    }
+    writer.flush()
+}
+
+//Reads images with 4, 8 or 16 byte words. Every word has to fit into W.
+pub fn read_binary<W: Word, R: Read>(mut reader: R) -> io::Result<Vec<W>> {
+    let mut header = [0; 6];
+    reader.read_exact(&mut header)?;
+    if &header[..4] != MAGIC {
+        return Err(invalid("not an intcode image".to_string()));
+    }
+    if header[4] != VERSION {
+        return Err(invalid(format!("unsupported image version {}", header[4])));
+    }
+    let size = header[5] as usize;
+    if ![4, 8, 16].contains(&size) {
+        return Err(invalid(format!("unsupported word size {}", size)));
+    }
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() % size != 0 {
+        return Err(invalid("image is truncated".to_string()));
+    }
+    bytes
+        .chunks(size)
+        .enumerate()
+        .map(|(index, chunk)| {
+            //Sign extend to 16 bytes
+            let fill = if chunk[size - 1] & 0x80 != 0 { 0xff } else { 0 };
+            let mut wide = [fill; 16];
+            wide[..size].copy_from_slice(chunk);
+            W::from_i128(i128::from_le_bytes(wide))
+                .ok_or_else(|| invalid(format!("word {} does not fit", index)))
+        })
+        .collect()
+}
+
+//Loads a text or binary program, the format is detected from the header.
+pub fn load<W: Word, P: AsRef<Path>>(path: P) -> io::Result<Vec<W>> {
+    read(File::open(path)?)
+}
+
+//Like load, for programs that do not come from a file.
+pub fn read<W: Word, R: Read>(mut reader: R) -> io::Result<Vec<W>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.starts_with(MAGIC) {
+        return read_binary(&bytes[..]);
+    }
+    let text = String::from_utf8(bytes).map_err(|_| invalid("program is no text".to_string()))?;
+    parse(&text).map_err(|err| invalid(err.to_string()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn separators_and_comments() {
+        let text = "# header\n1,2, 3\n\n4 5 # five\n  -6,\n";
+        assert_eq!(parse::<i64>(text).unwrap(), vec![1, 2, 3, 4, 5, -6]);
+        assert_eq!(parse::<i32>("1,2,3\n").unwrap(), vec![1, 2, 3]);
+        assert_eq!(parse::<i64>("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn diagnostics() {
+        let err = parse::<i64>("1,2\n3, x4,5").unwrap_err();
+        assert_eq!(err.to_string(), "2:4: value 3 \"x4\" is no valid number");
+        let err = parse::<i64>("1,2,,3").unwrap_err();
+        assert_eq!(err.to_string(), "1:5: value 2 is missing");
+        let err = parse::<i32>("1, 3000000000").unwrap_err();
+        assert_eq!((err.line, err.column, err.index), (1, 4, 1));
+    }
+
+    #[test]
+    fn binary_roundtrip() {
+        let image = vec![1i64, -2, 1 << 40, i64::MIN];
+        let mut bytes = Vec::new();
+        write_binary(&image, &mut bytes).unwrap();
+        assert_eq!(bytes.len(), 6 + 4 * 8);
+        assert_eq!(read_binary::<i64, _>(&bytes[..]).unwrap(), image);
+        assert_eq!(
+            read_binary::<i128, _>(&bytes[..]).unwrap(),
+            vec![1, -2, 1 << 40, i64::MIN as i128]
+        );
+        assert!(read_binary::<i32, _>(&bytes[..]).is_err());
+        assert!(read_binary::<i64, _>(&bytes[..bytes.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::HashMap;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::word::Word;
+
+const PAGE_BITS: usize = 10;
+pub(crate) const PAGE_SIZE: usize = 1 << PAGE_BITS;
+//Pages below this index live in a vector that grows on demand, everything above
+//is kept in a hash map so far away writes only allocate the pages they touch.
+const DENSE_PAGES: usize = 1024;
+
+pub(crate) type Page<W> = [W; PAGE_SIZE];
+
+//Owned intcode memory. Every address can be read and reads as 0 until written.
+//Pages are shared between clones and only copied when one of them writes.
+#[derive(Clone, Default)]
+pub struct Memory<W = i64> {
+    dense: Vec<Option<Arc<Page<W>>>>,
+    sparse: HashMap<usize, Arc<Page<W>>>,
+    //Returned for reads from pages that were never written
+    zero: W,
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new() -> Self {
+        Memory::default()
+    }
+
+    pub fn set(&mut self, address: usize, value: W) {
+        if value == W::ZERO && self.page(address >> PAGE_BITS).is_none() {
+            return;
+        }
+        self[address] = value;
+    }
+
+    //Number of words in image, without building it.
+    pub fn extent(&self) -> usize {
+        self.pages()
+            .iter()
+            .rev()
+            .find_map(|(index, page)| {
+                let last = page.iter().rposition(|&value| value != W::ZERO)?;
+                Some(index * PAGE_SIZE + last + 1)
+            })
+            .unwrap_or(0)
+    }
+
+    //Every word from address 0 up to the last one that is not 0.
+    pub fn image(&self) -> Vec<W> {
+        let mut image = Vec::new();
+        for (index, page) in self.pages() {
+            if let Some(last) = page.iter().rposition(|&value| value != W::ZERO) {
+                image.resize(index * PAGE_SIZE, W::ZERO);
+                image.extend_from_slice(&page[..=last]);
+            }
+        }
+        image
+    }
+
+    //All allocated pages ordered by their index.
+    pub(crate) fn pages(&self) -> Vec<(usize, &Page<W>)> {
+        let mut pages = self
+            .dense
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| page.as_deref().map(|page| (index, page)))
+            .chain(self.sparse.iter().map(|(&index, page)| (index, &**page)))
+            .collect::<Vec<_>>();
+        pages.sort_by_key(|&(index, _)| index);
+        pages
+    }
+
+    pub(crate) fn insert_page(&mut self, index: usize, page: Page<W>) {
+        *self.page_mut(index) = page;
+    }
+
+    fn page(&self, index: usize) -> Option<&Page<W>> {
+        if index < DENSE_PAGES {
+            self.dense.get(index).and_then(|page| page.as_deref())
+        } else {
+            self.sparse.get(&index).map(|page| &**page)
+        }
+    }
+
+    fn page_mut(&mut self, index: usize) -> &mut Page<W> {
+        if index < DENSE_PAGES {
+            if self.dense.len() <= index {
+                self.dense.resize_with(index + 1, || None);
+            }
+            Arc::make_mut(self.dense[index].get_or_insert_with(|| Arc::new([W::ZERO; PAGE_SIZE])))
+        } else {
+            Arc::make_mut(
+                self.sparse
+                    .entry(index)
+                    .or_insert_with(|| Arc::new([W::ZERO; PAGE_SIZE])),
+            )
+        }
+    }
+}
+
+//Memories are equal if every address reads the same. Pages still shared by
+//clones are not compared word by word.
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Self) -> bool {
+        let covered = |a: &Self, b: &Self| {
+            a.pages()
+                .into_iter()
+                .all(|(index, page)| match b.page(index) {
+                    Some(other) => std::ptr::eq(page, other) || page[..] == other[..],
+                    None => page.iter().all(|&value| value == W::ZERO),
+                })
+        };
+        covered(self, other) && covered(other, self)
+    }
+}
+
+impl<W: Word> Eq for Memory<W> {}
+
+impl<W: Word> From<&[W]> for Memory<W> {
+    fn from(program: &[W]) -> Self {
+        let mut memory = Memory::new();
+        for (address, &value) in program.iter().enumerate() {
+            memory.set(address, value);
+        }
+        memory
+    }
+}
+
+impl<W: Word> Index<usize> for Memory<W> {
+    type Output = W;
+
+    fn index(&self, address: usize) -> &W {
+        match self.page(address >> PAGE_BITS) {
+            Some(page) => &page[address & (PAGE_SIZE - 1)],
+            None => &self.zero,
+        }
+    }
+}
+
+impl<W: Word> IndexMut<usize> for Memory<W> {
+    fn index_mut(&mut self, address: usize) -> &mut W {
+        &mut self.page_mut(address >> PAGE_BITS)[address & (PAGE_SIZE - 1)]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reads_zero_past_the_end() {
+        let memory = Memory::from(&[1, 2, 3][..]);
+        assert_eq!(memory[2], 3);
+        assert_eq!(memory[3], 0);
+        assert_eq!(memory[1 << 40], 0);
+    }
+
+    #[test]
+    fn far_writes_are_sparse() {
+        let mut memory = Memory::new();
+        memory[1_000_000_000_000] = 5;
+        memory.set(7, 8);
+        memory.set(1 << 50, 0);
+        assert_eq!(memory[1_000_000_000_000], 5);
+        assert_eq!(memory[7], 8);
+        assert_eq!(memory.dense.len(), 1);
+        assert_eq!(memory.sparse.len(), 1);
+    }
+
+    #[test]
+    fn image_ends_at_last_value() {
+        let mut memory = Memory::from(&[1, 0, 3, 0][..]);
+        assert_eq!(memory.image(), vec![1, 0, 3]);
+        memory[PAGE_SIZE + 1] = 7;
+        memory[3 * PAGE_SIZE] = 0;
+        let image = memory.image();
+        assert_eq!(image.len(), PAGE_SIZE + 2);
+        assert_eq!(image[PAGE_SIZE + 1], 7);
+        assert_eq!(memory.extent(), image.len());
+        assert_eq!(Memory::<i64>::new().image(), vec![]);
+        assert_eq!(Memory::<i64>::new().extent(), 0);
+    }
+
+    #[test]
+    fn equality_ignores_zero_pages() {
+        let mut memory = Memory::from(&[1, 2, 3][..]);
+        let mut other = memory.clone();
+        other[5000] = 0;
+        assert!(memory == other);
+        memory[2] = 4;
+        assert!(memory != other);
+    }
+
+    #[test]
+    fn clones_copy_on_write() {
+        let mut memory = Memory::from(&[1, 2, 3][..]);
+        memory[5000] = 4;
+        let mut clone = memory.clone();
+        clone[1] = 7;
+        assert_eq!(memory[1], 2);
+        assert_eq!(clone[1], 7);
+        let shared = |index: usize| {
+            Arc::ptr_eq(
+                memory.dense[index].as_ref().unwrap(),
+                clone.dense[index].as_ref().unwrap(),
+            )
+        };
+        assert!(!shared(0));
+        assert!(shared(4));
+    }
+}
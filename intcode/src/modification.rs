@@ -0,0 +1,100 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+//What to do when a program modifies its own code.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Policy {
+    //Record every occurrence and keep running
+    Report,
+    //Fault with ErrorKind::SelfModification before anything is changed
+    Reject,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Modification {
+    //The instruction at pc wrote to an address that was executed before
+    Overwrite {
+        address: usize,
+        pc: usize,
+    },
+    //The instruction at pc covers an address written by the one at writer
+    Execute {
+        address: usize,
+        pc: usize,
+        writer: usize,
+    },
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Modification::Overwrite { address, pc } => {
+                write!(f, "pc {} overwrites executed address {}", pc, address)
+            }
+            Modification::Execute {
+                address,
+                pc,
+                writer,
+            } => write!(
+                f,
+                "pc {} executes address {} written by pc {}",
+                pc, address, writer
+            ),
+        }
+    }
+}
+
+//Remembers which words were executed and which were written at runtime.
+//Every word of an instruction counts, parameters included.
+#[derive(Clone)]
+pub(crate) struct Tracker {
+    pub policy: Policy,
+    pub found: Vec<Modification>,
+    executed: HashSet<usize>,
+    //Written addresses that have not been executed since, with the writing pc
+    written: HashMap<usize, usize>,
+}
+
+impl Tracker {
+    pub fn new(policy: Policy) -> Self {
+        Tracker {
+            policy,
+            found: Vec::new(),
+            executed: HashSet::new(),
+            written: HashMap::new(),
+        }
+    }
+
+    pub fn executing(&mut self, pc: usize, size: usize) -> Result<(), Modification> {
+        for address in pc..pc + size {
+            if let Some(&writer) = self.written.get(&address) {
+                self.found(Modification::Execute {
+                    address,
+                    pc,
+                    writer,
+                })?;
+                self.written.remove(&address);
+            }
+        }
+        self.executed.extend(pc..pc + size);
+        Ok(())
+    }
+
+    pub fn writing(&mut self, pc: usize, address: usize) -> Result<(), Modification> {
+        if self.executed.contains(&address) {
+            self.found(Modification::Overwrite { address, pc })?;
+        }
+        self.written.insert(address, pc);
+        Ok(())
+    }
+
+    fn found(&mut self, modification: Modification) -> Result<(), Modification> {
+        match self.policy {
+            Policy::Report => {
+                self.found.push(modification);
+                Ok(())
+            }
+            Policy::Reject => Err(modification),
+        }
+    }
+}
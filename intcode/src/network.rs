@@ -0,0 +1,203 @@
+use std::collections::VecDeque;
+
+use crate::computer::{Computer, State};
+use crate::error::IntcodeError;
+use crate::word::Word;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Packet<W = i64> {
+    pub dest: W,
+    pub x: W,
+    pub y: W,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Event<W = i64> {
+    //Packet for an address without a machine, e.g. a monitor
+    Packet(Packet<W>),
+    //A whole round went by without any machine sending or receiving a packet
+    Idle,
+}
+
+//Machines with the addresses 0 to size - 1 running the same program. Every
+//round runs each machine in address order until it waits for input again, so
+//the simulation is deterministic.
+pub struct Network<W = i64> {
+    machines: Vec<Computer<W>>,
+    queues: Vec<VecDeque<Packet<W>>>,
+    //Output values of a packet that is not complete yet
+    partial: Vec<Vec<W>>,
+    halted: Vec<bool>,
+}
+
+impl<W: Word> Network<W> {
+    pub fn new(program: &[W], size: usize) -> Self {
+        let machines = (0..size)
+            .map(|address| {
+                let mut machine = Computer::from_program(program);
+                let address = W::from_i64(address as i64).expect("address does not fit a word");
+                machine.push_input(address);
+                machine
+            })
+            .collect::<Vec<_>>();
+        Network {
+            machines,
+            queues: vec![VecDeque::new(); size],
+            partial: vec![Vec::new(); size],
+            halted: vec![false; size],
+        }
+    }
+
+    pub fn machines(&self) -> &[Computer<W>] {
+        &self.machines
+    }
+
+    //Queues the packet for its destination. Returns the packet if there is no
+    //machine with that address.
+    pub fn send(&mut self, packet: Packet<W>) -> Option<Packet<W>> {
+        match packet.dest.to_address() {
+            Ok(address) if address < self.queues.len() => {
+                self.queues[address].push_back(packet);
+                None
+            }
+            _ => Some(packet),
+        }
+    }
+
+    //Runs every machine once. Machines without pending packets read -1.
+    pub fn round(&mut self) -> Result<Vec<Event<W>>, IntcodeError> {
+        let no_packet = W::from_i64(-1).unwrap();
+        let mut events = Vec::new();
+        let mut busy = false;
+        for address in 0..self.machines.len() {
+            if self.halted[address] {
+                continue;
+            }
+            let machine = &mut self.machines[address];
+            if self.queues[address].is_empty() {
+                machine.push_input(no_packet);
+            }
+            for packet in self.queues[address].drain(..) {
+                machine.push_input(packet.x);
+                machine.push_input(packet.y);
+                busy = true;
+            }
+            let mut sent = Vec::new();
+            loop {
+                match machine.resume()? {
+                    State::Output(value) => {
+                        let partial = &mut self.partial[address];
+                        partial.push(value);
+                        if partial.len() == 3 {
+                            sent.push(Packet {
+                                dest: partial[0],
+                                x: partial[1],
+                                y: partial[2],
+                            });
+                            partial.clear();
+                        }
+                    }
+                    State::Halted => {
+                        self.halted[address] = true;
+                        break;
+                    }
+                    State::NeedsInput => break,
+                }
+            }
+            busy |= !sent.is_empty();
+            for packet in sent {
+                if let Some(packet) = self.send(packet) {
+                    events.push(Event::Packet(packet));
+                }
+            }
+        }
+        if !busy {
+            events.push(Event::Idle);
+        }
+        Ok(events)
+    }
+
+    //Runs rounds and hands every event to monitor, which may send packets
+    //itself, until it returns a result. Returns None once all machines halted.
+    pub fn run<T>(
+        &mut self,
+        mut monitor: impl FnMut(&mut Self, Event<W>) -> Option<T>,
+    ) -> Result<Option<T>, IntcodeError> {
+        while self.halted.iter().any(|halted| !halted) {
+            for event in self.round()? {
+                if let Some(result) = monitor(self, event) {
+                    return Ok(Some(result));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::asm::assemble;
+
+    //Machine 0 sends (1, 7, 8), every machine forwards what it gets to the next
+    //address with y incremented.
+    const RELAY: &str = "
+                IN [addr]
+                JF [addr], #first
+        wait:   IN [x]
+                EQ [x], #-1, [tmp]
+                JT [tmp], #wait
+                IN [y]
+                ADD [addr], #1, [dest]
+                OUT [dest]
+                OUT [x]
+                ADD [y], #1, [y]
+                OUT [y]
+                JT #1, #wait
+        first:  OUT #1
+                OUT #7
+                OUT #8
+                JT #1, #wait
+        addr:   data 0
+        x:      data 0
+        y:      data 0
+        dest:   data 0
+        tmp:    data 0
+    ";
+
+    #[test]
+    fn relay_and_idle() {
+        let program = assemble(RELAY).unwrap();
+        let mut network = Network::new(&program, 3);
+        let mut events = Vec::new();
+        let result = network
+            .run(|network, event| {
+                events.push(event);
+                match event {
+                    Event::Idle if events.len() == 2 => {
+                        network.send(Packet {
+                            dest: 0,
+                            x: 1,
+                            y: 1,
+                        });
+                        None
+                    }
+                    Event::Packet(packet) if packet.x == 1 => Some(packet.y),
+                    _ => None,
+                }
+            })
+            .unwrap();
+        assert_eq!(result, Some(4));
+        assert_eq!(
+            events[..2],
+            [
+                Event::Packet(Packet {
+                    dest: 3,
+                    x: 7,
+                    y: 10
+                }),
+                Event::Idle
+            ]
+        );
+    }
+}
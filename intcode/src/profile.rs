@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::cache::Decoded;
+use crate::computer::opcode_info;
+use crate::disasm::{decode, Line};
+use crate::word::Word;
+
+//Execution counters collected while profiling is enabled. Instructions that
+//wait for input are only counted once they actually run.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Profile {
+    pub instructions: u64,
+    pub pcs: HashMap<usize, u64>,
+    //Instruction word and parameters last executed at every pc, so the report
+    //shows the code that ran even if the program overwrote it later. Wide
+    //parameters that do not fit an i64 are clamped.
+    pub code: HashMap<usize, [i64; 4]>,
+    pub opcodes: HashMap<i64, u64>,
+    //Parameters executed in position, immediate and relative mode
+    pub modes: [u64; 3],
+    pub reads: HashMap<usize, u64>,
+    pub writes: HashMap<usize, u64>,
+    //Taken jumps that go backwards, keyed by (jump pc, target)
+    pub loops: HashMap<(usize, usize), u64>,
+}
+
+fn hottest<K: Copy + Ord>(counts: &HashMap<K, u64>, top: usize) -> Vec<(K, u64)> {
+    let mut counts = counts
+        .iter()
+        .map(|(&key, &count)| (key, count))
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts.truncate(top);
+    counts
+}
+
+impl Profile {
+    pub(crate) fn count_instruction<W: Word>(&mut self, pc: usize, decoded: &Decoded<W>) {
+        self.instructions += 1;
+        *self.pcs.entry(pc).or_insert(0) += 1;
+        *self.opcodes.entry(decoded.opcode).or_insert(0) += 1;
+        let mut words = [decoded.opcode, 0, 0, 0];
+        let mut scale = 100;
+        for i in 0..decoded.size - 1 {
+            let mode = decoded.modes[i];
+            self.modes[mode as usize] += 1;
+            words[0] += scale * mode as i64;
+            scale *= 10;
+            let param = decoded.params[i].to_i128();
+            words[i + 1] = param.clamp(i64::MIN as i128, i64::MAX as i128) as i64;
+        }
+        self.code.insert(pc, words);
+    }
+
+    pub(crate) fn count_read(&mut self, address: usize) {
+        *self.reads.entry(address).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_write(&mut self, address: usize) {
+        *self.writes.entry(address).or_insert(0) += 1;
+    }
+
+    pub(crate) fn count_jump(&mut self, from: usize, to: usize) {
+        if to <= from {
+            *self.loops.entry((from, to)).or_insert(0) += 1;
+        }
+    }
+
+    //Human readable summary with the top entries of every table, hottest
+    //first. Hot instructions are disassembled as they were last executed.
+    pub fn report(&self, top: usize) -> String {
+        let mut report = String::new();
+        let share = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+        writeln!(report, "instructions: {}", self.instructions).unwrap();
+
+        writeln!(report, "opcodes:").unwrap();
+        for (opcode, count) in hottest(&self.opcodes, usize::MAX) {
+            let mnemonic = opcode_info(opcode).map_or("?", |info| info.0);
+            writeln!(
+                report,
+                "  {:<6}{:>12} {:>6.2}%",
+                mnemonic,
+                count,
+                share(count)
+            )
+            .unwrap();
+        }
+        writeln!(
+            report,
+            "modes: position {}, immediate {}, relative {}",
+            self.modes[0], self.modes[1], self.modes[2]
+        )
+        .unwrap();
+
+        writeln!(report, "loops:").unwrap();
+        for ((from, to), count) in hottest(&self.loops, top) {
+            writeln!(report, "  {:04}..{:04}{:>12} iterations", to, from, count).unwrap();
+        }
+        writeln!(report, "hot instructions:").unwrap();
+        for (pc, count) in hottest(&self.pcs, top) {
+            let words = &self.code[&pc];
+            let line = match decode(words, 0) {
+                Some(Line::Instruction {
+                    word,
+                    mnemonic,
+                    operands,
+                    ..
+                }) => Line::Instruction {
+                    address: pc,
+                    word,
+                    mnemonic,
+                    operands,
+                },
+                _ => Line::Data {
+                    address: pc,
+                    value: words[0],
+                },
+            };
+            writeln!(report, "  {:>12} {:>6.2}%  {}", count, share(count), line).unwrap();
+        }
+        for (title, counts) in &[("reads", &self.reads), ("writes", &self.writes)] {
+            writeln!(report, "hot {}:", title).unwrap();
+            for (address, count) in hottest(counts, top) {
+                writeln!(report, "  {:04}{:>12}", address, count).unwrap();
+            }
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::computer::Computer;
+    use crate::fixtures;
+    use std::collections::VecDeque;
+
+    #[test]
+    fn counts_countdown() {
+        let mut computer = Computer::new(&fixtures::countdown());
+        computer.enable_profiling();
+        computer.run(&mut VecDeque::new(), &mut Vec::new()).unwrap();
+        let profile = computer.take_profile().unwrap();
+
+        assert_eq!(profile.instructions, 10);
+        assert_eq!(profile.pcs[&0], 3);
+        assert_eq!(profile.opcodes[&99], 1);
+        assert_eq!(profile.modes, [12, 6, 0]);
+        assert_eq!(profile.reads[&20], 9);
+        assert_eq!(profile.reads[&8], 2);
+        assert_eq!(profile.writes[&20], 3);
+        assert_eq!(profile.loops[&(6, 0)], 2);
+
+        let report = profile.report(2);
+        assert!(report.starts_with("instructions: 10\n"));
+        assert!(report.contains("  0000..0006           2 iterations\n"));
+        assert!(report.contains("           3  30.00%  0000: OUT [20]\n"));
+    }
+
+    #[test]
+    fn reports_executed_code() {
+        //Overwrites its own first instruction with 3
+        let mut computer = Computer::new(&[1101, 1, 2, 0, 99]);
+        computer.enable_profiling();
+        computer.run(&mut VecDeque::new(), &mut Vec::new()).unwrap();
+        assert_eq!(computer.memory()[0], 3);
+
+        let report = computer.take_profile().unwrap().report(1);
+        assert!(report.contains("  50.00%  0000: ADD #1, #2, [0]\n"));
+    }
+}
@@ -0,0 +1,252 @@
+use std::collections::VecDeque;
+use std::convert::TryInto;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::computer::{Arithmetic, Computer};
+use crate::memory::{Memory, PAGE_SIZE};
+use crate::word::Word;
+
+const MAGIC: &[u8; 4] = b"ICSN";
+//Version 2 added the arithmetic policy.
+const VERSION: u32 = 2;
+
+//Complete machine state plus outputs the caller has not consumed yet.
+//Tracers are not part of a snapshot.
+pub struct Snapshot<W = i64> {
+    pub computer: Computer<W>,
+    pub output: Vec<W>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+//64 bit FNV-1a over the payload.
+fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn arithmetic_code(arithmetic: Arithmetic) -> u64 {
+    match arithmetic {
+        Arithmetic::Checked => 0,
+        Arithmetic::Saturating => 1,
+        Arithmetic::Wrapping => 2,
+    }
+}
+
+fn put(buf: &mut Vec<u8>, value: u64) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_values<'a>(buf: &mut Vec<u8>, values: impl ExactSizeIterator<Item = &'a i64>) {
+    put(buf, values.len() as u64);
+    for &value in values {
+        put(buf, value as u64);
+    }
+}
+
+struct Cursor<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Cursor<'a> {
+    fn take(&mut self) -> io::Result<u64> {
+        if self.bytes.len() < 8 {
+            return Err(invalid("snapshot is truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(8);
+        self.bytes = rest;
+        Ok(u64::from_le_bytes(head.try_into().unwrap()))
+    }
+
+    fn count(&mut self) -> io::Result<usize> {
+        let count = self.take()? as usize;
+        if count > self.bytes.len() / 8 {
+            return Err(invalid("snapshot is truncated"));
+        }
+        Ok(count)
+    }
+
+    fn values(&mut self) -> io::Result<Vec<i64>> {
+        (0..self.count()?)
+            .map(|_| self.take().map(|value| value as i64))
+            .collect()
+    }
+}
+
+impl<W: Word> Snapshot<W> {
+    pub fn new(computer: Computer<W>) -> Self {
+        Snapshot {
+            computer,
+            output: Vec::new(),
+        }
+    }
+}
+
+//The file format stores 64 bit words, so only i64 machines can be saved.
+impl Snapshot {
+    //Layout: magic, version, payload length, payload, checksum of the payload.
+    //All numbers are little-endian 64 bit except the 32 bit version.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let computer = &self.computer;
+        let mut payload = Vec::new();
+        put(&mut payload, computer.pc() as u64);
+        put(&mut payload, computer.relative_base() as u64);
+        put(&mut payload, arithmetic_code(computer.arithmetic()));
+        put_values(&mut payload, computer.pending_input().iter());
+        put_values(&mut payload, self.output.iter());
+        let pages = computer.memory().pages();
+        put(&mut payload, pages.len() as u64);
+        for (index, page) in pages {
+            put(&mut payload, index as u64);
+            for &value in page.iter() {
+                put(&mut payload, value as u64);
+            }
+        }
+
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+        writer.write_all(&payload)?;
+        writer.write_all(&checksum(&payload).to_le_bytes())?;
+        writer.flush()
+    }
+
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 16];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not an intcode snapshot"));
+        }
+        let version = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "unsupported snapshot version {}",
+                version
+            )));
+        }
+        let len = u64::from_le_bytes(header[8..].try_into().unwrap());
+        let mut payload = Vec::new();
+        reader.by_ref().take(len).read_to_end(&mut payload)?;
+        let mut sum = [0; 8];
+        if payload.len() as u64 != len || reader.read_exact(&mut sum).is_err() {
+            return Err(invalid("snapshot is truncated"));
+        }
+        if u64::from_le_bytes(sum) != checksum(&payload) {
+            return Err(invalid("snapshot checksum does not match"));
+        }
+
+        let mut cursor = Cursor { bytes: &payload };
+        let pc = cursor.take()? as usize;
+        let relative_base = cursor.take()? as i64;
+        let arithmetic = match cursor.take()? {
+            0 => Arithmetic::Checked,
+            1 => Arithmetic::Saturating,
+            2 => Arithmetic::Wrapping,
+            _ => return Err(invalid("unknown arithmetic policy")),
+        };
+        let input = cursor.values()?.into_iter().collect::<VecDeque<_>>();
+        let output = cursor.values()?;
+        let mut memory = Memory::new();
+        for _ in 0..cursor.count()? {
+            let index = cursor.take()? as usize;
+            let mut page = [0; PAGE_SIZE];
+            for value in page.iter_mut() {
+                *value = cursor.take()? as i64;
+            }
+            memory.insert_page(index, page);
+        }
+        if !cursor.bytes.is_empty() {
+            return Err(invalid("trailing data in snapshot"));
+        }
+        Ok(Snapshot {
+            computer: Computer::from_state(pc, relative_base, memory, input, arithmetic),
+            output,
+        })
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_to(BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Snapshot::read_from(BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::computer::State;
+
+    //Echoes every input until it reads 0, with a write far away on the way
+    const ECHO: [i64; 15] = [
+        3, 100, 4, 100, 1006, 100, 14, 1101, 1, 0, 5000, 1105, 1, 0, 99,
+    ];
+
+    fn bytes(snapshot: &Snapshot) -> Vec<u8> {
+        let mut buf = Vec::new();
+        snapshot.write_to(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn resumed_run_matches() {
+        let mut computer = Computer::new(&ECHO);
+        computer.push_input(7);
+        assert_eq!(computer.resume().unwrap(), State::Output(7));
+        computer.push_input(8);
+        computer.push_input(0);
+
+        let mut snapshot = Snapshot::new(computer);
+        snapshot.output.push(7);
+        let saved = bytes(&snapshot);
+        let mut restored = Snapshot::read_from(&saved[..]).unwrap();
+        assert_eq!(bytes(&restored), saved);
+        assert_eq!(restored.output, vec![7]);
+        assert_eq!(restored.computer.memory()[5000], 0);
+
+        for computer in [&mut snapshot.computer, &mut restored.computer].iter_mut() {
+            assert_eq!(computer.resume().unwrap(), State::Output(8));
+            assert_eq!(computer.resume().unwrap(), State::Output(0));
+            assert_eq!(computer.resume().unwrap(), State::Halted);
+        }
+        assert_eq!(bytes(&snapshot), bytes(&restored));
+        assert_eq!(restored.computer.memory()[5000], 1);
+    }
+
+    #[test]
+    fn keeps_arithmetic() {
+        //Reads a value and adds i64::MAX to it
+        let program = [3, 9, 1001, 9, i64::MAX, 9, 4, 9, 99, 0];
+        let mut computer = Computer::new(&program);
+        computer.set_arithmetic(Arithmetic::Wrapping);
+        assert_eq!(computer.resume().unwrap(), State::NeedsInput);
+
+        let mut restored = Snapshot::read_from(&bytes(&Snapshot::new(computer))[..]).unwrap();
+        assert_eq!(restored.computer.arithmetic(), Arithmetic::Wrapping);
+        restored.computer.push_input(2);
+        assert_eq!(
+            restored.computer.resume().unwrap(),
+            State::Output(i64::MIN + 1)
+        );
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let saved = bytes(&Snapshot::new(Computer::new(&ECHO)));
+        let mut corrupted = saved.clone();
+        corrupted[20] ^= 1;
+        let err = Snapshot::read_from(&corrupted[..]).err().unwrap();
+        assert_eq!(err.to_string(), "snapshot checksum does not match");
+
+        let mut future = saved.clone();
+        future[4] = 3;
+        assert!(Snapshot::read_from(&future[..]).is_err());
+        assert!(Snapshot::read_from(&saved[..saved.len() - 1]).is_err());
+    }
+}
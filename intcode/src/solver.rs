@@ -0,0 +1,610 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+
+use crate::computer::{opcode_info, split_instruction, Computer, Mode};
+use crate::error::ErrorKind;
+use crate::limits::{Limits, Outcome};
+use crate::word::Word;
+
+//Patched programs can loop forever, so every run gets this many instructions
+//unless the limits are changed.
+const DEFAULT_INSTRUCTIONS: u64 = 1 << 20;
+
+//Sum of products of patched cells. Monomials are the sorted addresses of their
+//cells, the empty monomial is the constant term. Zero coefficients are dropped.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Polynomial {
+    terms: BTreeMap<Vec<usize>, i128>,
+}
+
+impl Polynomial {
+    pub fn constant(value: i128) -> Self {
+        let mut terms = BTreeMap::new();
+        if value != 0 {
+            terms.insert(vec![], value);
+        }
+        Polynomial { terms }
+    }
+
+    pub fn cell(address: usize) -> Self {
+        let mut terms = BTreeMap::new();
+        terms.insert(vec![address], 1);
+        Polynomial { terms }
+    }
+
+    pub fn as_constant(&self) -> Option<i128> {
+        match self.terms.iter().next() {
+            None => Some(0),
+            Some((monomial, &value)) if monomial.is_empty() && self.terms.len() == 1 => Some(value),
+            _ => None,
+        }
+    }
+
+    fn add_term(&mut self, monomial: Vec<usize>, value: i128) -> Option<()> {
+        let sum = self
+            .terms
+            .get(&monomial)
+            .copied()
+            .unwrap_or(0)
+            .checked_add(value)?;
+        if sum == 0 {
+            self.terms.remove(&monomial);
+        } else {
+            self.terms.insert(monomial, sum);
+        }
+        Some(())
+    }
+
+    pub fn checked_add(&self, other: &Self) -> Option<Self> {
+        let mut sum = self.clone();
+        for (monomial, &value) in &other.terms {
+            sum.add_term(monomial.clone(), value)?;
+        }
+        Some(sum)
+    }
+
+    pub fn checked_mul(&self, other: &Self) -> Option<Self> {
+        let mut product = Polynomial::default();
+        for (a, &x) in &self.terms {
+            for (b, &y) in &other.terms {
+                let mut monomial = a.iter().chain(b).copied().collect::<Vec<_>>();
+                monomial.sort_unstable();
+                product.add_term(monomial, x.checked_mul(y)?)?;
+            }
+        }
+        Some(product)
+    }
+
+    //Highest power of the cell at address in any term.
+    pub fn degree(&self, address: usize) -> usize {
+        self.terms
+            .keys()
+            .map(|monomial| monomial.iter().filter(|&&cell| cell == address).count())
+            .max()
+            .unwrap_or(0)
+    }
+
+    //Splits a polynomial of degree 1 in address into a and b with
+    //self = a * [address] + b.
+    fn split(&self, address: usize) -> (Polynomial, Polynomial) {
+        let mut a = Polynomial::default();
+        let mut b = Polynomial::default();
+        for (monomial, &value) in &self.terms {
+            match monomial.iter().position(|&cell| cell == address) {
+                Some(index) => {
+                    let mut rest = monomial.clone();
+                    rest.remove(index);
+                    a.terms.insert(rest, value);
+                }
+                None => {
+                    b.terms.insert(monomial.clone(), value);
+                }
+            }
+        }
+        (a, b)
+    }
+
+    //None if a cell is missing from values or the result overflows.
+    pub fn evaluate(&self, values: &HashMap<usize, i128>) -> Option<i128> {
+        self.terms
+            .iter()
+            .try_fold(0i128, |sum, (monomial, &value)| {
+                let term = monomial.iter().try_fold(value, |product, cell| {
+                    product.checked_mul(*values.get(cell)?)
+                })?;
+                sum.checked_add(term)
+            })
+    }
+}
+
+impl fmt::Display for Polynomial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (index, (monomial, &value)) in self.terms.iter().enumerate() {
+            match (index, value < 0) {
+                (0, true) => write!(f, "-")?,
+                (0, false) => {}
+                (_, true) => write!(f, " - ")?,
+                (_, false) => write!(f, " + ")?,
+            }
+            let factor = value.unsigned_abs();
+            if monomial.is_empty() || factor != 1 {
+                write!(f, "{}", factor)?;
+            }
+            for (index, cell) in monomial.iter().enumerate() {
+                if index > 0 || factor != 1 {
+                    write!(f, "*")?;
+                }
+                write!(f, "[{}]", cell)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+//Reasons why a search could not be done. All but TooLarge only concern the
+//symbolic search, the exhaustive one still works in these cases.
+#[derive(Debug)]
+pub enum SearchError {
+    //There are more than 2^64 combinations
+    TooLarge,
+    //Only result targets can be solved, not predicates
+    Predicate,
+    //A patched cell decides an instruction, an address or a jump
+    Depends { pc: usize },
+    //The program reads input, a search has none to give
+    Input { pc: usize },
+    Fault { pc: usize, kind: ErrorKind },
+    Limit,
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SearchError::TooLarge => write!(f, "too many combinations to search"),
+            SearchError::Predicate => write!(f, "predicates can not be solved symbolically"),
+            SearchError::Depends { pc } => {
+                write!(f, "control flow at pc {} depends on a patched cell", pc)
+            }
+            SearchError::Input { pc } => write!(f, "input read at pc {}", pc),
+            SearchError::Fault { pc, kind } => write!(f, "fault at pc {}: {}", pc, kind),
+            SearchError::Limit => write!(f, "instruction limit reached"),
+        }
+    }
+}
+
+impl Error for SearchError {}
+
+//Memory of the symbolic machine. None is a value that depends on the patched
+//cells in an unknown way, like a read through a patched address. It is fine
+//as long as it is overwritten before anything uses it.
+struct Symbolic<'a, W> {
+    program: &'a [W],
+    written: HashMap<usize, Option<Polynomial>>,
+    pc: usize,
+    relative_base: i128,
+}
+
+impl<'a, W: Word> Symbolic<'a, W> {
+    fn read(&self, address: usize) -> Option<Polynomial> {
+        match self.written.get(&address) {
+            Some(value) => value.clone(),
+            None => Some(Polynomial::constant(
+                self.program.get(address).map_or(0, |value| value.to_i128()),
+            )),
+        }
+    }
+
+    fn constant(&self, value: Option<Polynomial>) -> Result<i128, SearchError> {
+        value
+            .and_then(|value| value.as_constant())
+            .ok_or(SearchError::Depends { pc: self.pc })
+    }
+
+    fn address(&self, value: i128) -> Result<usize, SearchError> {
+        value
+            .to_address()
+            .map_err(|kind| SearchError::Fault { pc: self.pc, kind })
+    }
+
+    //Address of a position or relative parameter, None if it is patched.
+    fn param_address(&self, index: usize, mode: Mode) -> Result<Option<usize>, SearchError> {
+        let raw = match self
+            .read(self.pc + 1 + index)
+            .and_then(|raw| raw.as_constant())
+        {
+            Some(raw) => raw,
+            None => return Ok(None),
+        };
+        let address = match mode {
+            Mode::Relative => raw
+                .checked_add(self.relative_base)
+                .ok_or(SearchError::Fault {
+                    pc: self.pc,
+                    kind: ErrorKind::Overflow,
+                })?,
+            _ => raw,
+        };
+        self.address(address).map(Some)
+    }
+
+    fn param(&self, index: usize, mode: Mode) -> Result<Option<Polynomial>, SearchError> {
+        if mode == Mode::Immediate {
+            return Ok(self.read(self.pc + 1 + index));
+        }
+        Ok(self
+            .param_address(index, mode)?
+            .and_then(|address| self.read(address)))
+    }
+
+    fn run(&mut self, limit: Option<u64>) -> Result<Option<Polynomial>, SearchError> {
+        let mut steps = 0;
+        loop {
+            if limit.is_some_and(|limit| steps >= limit) {
+                return Err(SearchError::Limit);
+            }
+            steps += 1;
+            let pc = self.pc;
+            let fault = |kind| SearchError::Fault { pc, kind };
+            let instruction = self.constant(self.read(pc))?;
+            let (opcode, mode1, mode2, mode3) = split_instruction(instruction).map_err(fault)?;
+            let modes = [mode1, mode2, mode3];
+            let (_, count, written) =
+                opcode_info(opcode).ok_or_else(|| fault(ErrorKind::UnknownOpcode(opcode)))?;
+            let mut next = pc + 1 + count;
+            let result = match opcode {
+                1 | 2 | 7 | 8 => {
+                    let a = self.param(0, modes[0])?;
+                    let b = self.param(1, modes[1])?;
+                    match (opcode, a, b) {
+                        (1, Some(a), Some(b)) => {
+                            Some(a.checked_add(&b).ok_or(fault(ErrorKind::Overflow))?)
+                        }
+                        (2, Some(a), Some(b)) => {
+                            Some(a.checked_mul(&b).ok_or(fault(ErrorKind::Overflow))?)
+                        }
+                        (1, _, _) | (2, _, _) => None,
+                        (_, a, b) => {
+                            let (a, b) = (self.constant(a)?, self.constant(b)?);
+                            let holds = if opcode == 7 { a < b } else { a == b };
+                            Some(Polynomial::constant(holds as i128))
+                        }
+                    }
+                }
+                3 => return Err(SearchError::Input { pc }),
+                4 => None,
+                5 | 6 => {
+                    let condition = self.constant(self.param(0, modes[0])?)?;
+                    if (condition != 0) == (opcode == 5) {
+                        let target = self.constant(self.param(1, modes[1])?)?;
+                        next = self.address(target)?;
+                    }
+                    None
+                }
+                9 => {
+                    let offset = self.constant(self.param(0, modes[0])?)?;
+                    self.relative_base = self
+                        .relative_base
+                        .checked_add(offset)
+                        .ok_or(fault(ErrorKind::Overflow))?;
+                    None
+                }
+                _ => return Ok(self.read(0)),
+            };
+            if let Some(index) = written {
+                if modes[index] == Mode::Immediate {
+                    return Err(fault(ErrorKind::WriteToImmediate));
+                }
+                let address = self
+                    .param_address(index, modes[index])?
+                    .ok_or(SearchError::Depends { pc })?;
+                self.written.insert(address, result);
+            }
+            self.pc = next;
+        }
+    }
+}
+
+type Cells = [(usize, RangeInclusive<i64>)];
+
+//Number of values of every cell and the number of combinations.
+fn space(cells: &Cells) -> Result<(Vec<u64>, u64), SearchError> {
+    let counts = cells
+        .iter()
+        .map(|(_, range)| {
+            let count = (*range.end() as i128 - *range.start() as i128 + 1).max(0);
+            u64::try_from(count).map_err(|_| SearchError::TooLarge)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let total = counts
+        .iter()
+        .try_fold(1u64, |total, &count| total.checked_mul(count))
+        .ok_or(SearchError::TooLarge)?;
+    Ok((counts, total))
+}
+
+//Values of the combination with this index, the last cell changes fastest.
+fn combination(cells: &Cells, counts: &[u64], mut index: u64) -> Vec<i64> {
+    let mut values = vec![0; cells.len()];
+    for ((value, (_, range)), &count) in values.iter_mut().zip(cells).zip(counts).rev() {
+        *value = (*range.start() as i128 + (index % count) as i128) as i64;
+        index /= count;
+    }
+    values
+}
+
+//What a run has to end with: a value at address 0 or anything a predicate
+//accepts, given the value at address 0 and the outputs.
+enum Goal<W> {
+    Result(W),
+    Predicate(Box<Predicate<W>>),
+}
+
+type Predicate<W> = dyn Fn(W, &[W]) -> bool + Send + Sync;
+
+//Finds values for patched memory cells that make a program halt with a goal,
+//like the noun and verb of day 2. Runs that fault or hit the limits count as
+//misses.
+pub struct Search<W = i64> {
+    program: Vec<W>,
+    goal: Goal<W>,
+    cells: Vec<(usize, RangeInclusive<i64>)>,
+    limits: Limits,
+    threads: usize,
+}
+
+impl<W: Word> Search<W> {
+    //Searches for runs that halt with target at address 0.
+    pub fn new(program: &[W], target: W) -> Self {
+        Search::with_goal(program, Goal::Result(target))
+    }
+
+    //Searches for runs whose result and outputs are accepted by predicate.
+    pub fn with_predicate<F>(program: &[W], predicate: F) -> Self
+    where
+        F: Fn(W, &[W]) -> bool + Send + Sync + 'static,
+    {
+        Search::with_goal(program, Goal::Predicate(Box::new(predicate)))
+    }
+
+    fn with_goal(program: &[W], goal: Goal<W>) -> Self {
+        Search {
+            program: program.to_vec(),
+            goal,
+            cells: Vec::new(),
+            limits: Limits {
+                instructions: Some(DEFAULT_INSTRUCTIONS),
+                ..Limits::default()
+            },
+            threads: thread::available_parallelism().map_or(1, |threads| threads.get()),
+        }
+    }
+
+    //Solutions list the cells in the order they were added.
+    pub fn add_cell(&mut self, address: usize, range: RangeInclusive<i64>) {
+        self.cells.push((address, range));
+    }
+
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    //Runs the program with the cells patched to values.
+    pub fn check(&self, values: &[i64]) -> bool {
+        let mut computer = Computer::from_program(&self.program);
+        for (&(address, _), &value) in self.cells.iter().zip(values) {
+            match W::from_i64(value) {
+                Some(value) => computer.memory_mut()[address] = value,
+                None => return false,
+            }
+        }
+        let mut output = Vec::new();
+        let outcome = computer.run_limited(&mut VecDeque::new(), &mut output, &self.limits);
+        match (outcome, &self.goal) {
+            (Ok(Outcome::Halted(result)), Goal::Result(target)) => result == *target,
+            (Ok(Outcome::Halted(result)), Goal::Predicate(predicate)) => predicate(result, &output),
+            _ => false,
+        }
+    }
+
+    //Only called with solutions, check already converted their values once.
+    fn to_words(values: &[i64]) -> Vec<W> {
+        values
+            .iter()
+            .map(|&value| W::from_i64(value).unwrap())
+            .collect()
+    }
+
+    //Tries every combination on all threads and returns the first solution
+    //in order, the same one a sequential search would find.
+    pub fn exhaustive(&self) -> Result<Option<Vec<W>>, SearchError> {
+        let (counts, total) = space(&self.cells)?;
+        let threads = self.threads as u64;
+        let best = AtomicU64::new(u64::MAX);
+        thread::scope(|scope| {
+            for first in 0..threads.min(total) {
+                let (best, counts) = (&best, &counts);
+                scope.spawn(move || {
+                    let mut index = first;
+                    //Later indices can not beat a solution that was found
+                    while index < total && index < best.load(Ordering::Relaxed) {
+                        if self.check(&combination(&self.cells, counts, index)) {
+                            best.fetch_min(index, Ordering::Relaxed);
+                            break;
+                        }
+                        index += threads;
+                    }
+                });
+            }
+        });
+        let best = best.into_inner();
+        if best < total {
+            Ok(Some(Self::to_words(&combination(
+                &self.cells,
+                &counts,
+                best,
+            ))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    //Runs the program with the cells as unknowns and returns the value it
+    //halts with.
+    pub fn expression(&self) -> Result<Polynomial, SearchError> {
+        let mut machine = Symbolic {
+            program: &self.program,
+            written: self
+                .cells
+                .iter()
+                .map(|&(address, _)| (address, Some(Polynomial::cell(address))))
+                .collect(),
+            pc: 0,
+            relative_base: 0,
+        };
+        machine
+            .run(self.limits.instructions)?
+            .ok_or(SearchError::Depends { pc: machine.pc })
+    }
+
+    //Solves the expression for the last cell it is linear in and enumerates
+    //the others. Candidates are confirmed by a real run, so overflows the
+    //expression does not see are still caught.
+    pub fn symbolic(&self) -> Result<Option<Vec<W>>, SearchError> {
+        let target = match self.goal {
+            Goal::Result(target) => target.to_i128(),
+            Goal::Predicate(_) => return Err(SearchError::Predicate),
+        };
+        let expression = self.expression()?;
+        let solved = self
+            .cells
+            .iter()
+            .rposition(|&(address, _)| expression.degree(address) <= 1);
+        let mut others = self.cells.clone();
+        let (a, b) = match solved {
+            Some(index) => expression.split(others.remove(index).0),
+            None => (Polynomial::default(), expression),
+        };
+
+        let (counts, total) = space(&others)?;
+        for combination in (0..total).map(|index| combination(&others, &counts, index)) {
+            let known = others
+                .iter()
+                .zip(&combination)
+                .map(|(&(address, _), &value)| (address, value as i128))
+                .collect::<HashMap<_, _>>();
+            let (a, b) = match (a.evaluate(&known), b.evaluate(&known)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            let index = match solved {
+                Some(index) => index,
+                None => {
+                    if b == target && self.check(&combination) {
+                        return Ok(Some(Self::to_words(&combination)));
+                    }
+                    continue;
+                }
+            };
+            let range = self.cells[index].1.clone();
+            let candidates: Box<dyn Iterator<Item = i64>> = if a == 0 {
+                if b != target {
+                    continue;
+                }
+                //The solved cell does not matter for these values of the others
+                Box::new(range)
+            } else {
+                let x = target
+                    .checked_sub(b)
+                    .filter(|difference| difference.checked_rem(a) == Some(0))
+                    .and_then(|difference| i64::try_from(difference.checked_div(a)?).ok())
+                    .filter(|x| range.contains(x));
+                Box::new(x.into_iter())
+            };
+            for x in candidates {
+                let mut values = combination.clone();
+                values.insert(index, x);
+                if self.check(&values) {
+                    return Ok(Some(Self::to_words(&values)));
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //Day 2 style: [0] = 100 * [1] + [2] + 5, with a first instruction that reads
+    //through the patched cells and is overwritten later
+    const PROGRAM: [i64; 17] = [1, 0, 0, 3, 1002, 1, 100, 0, 1, 0, 2, 0, 1001, 0, 5, 0, 99];
+
+    fn day2(target: i64) -> Search {
+        let mut search = Search::new(&PROGRAM, target);
+        search.add_cell(1, 0..=99);
+        search.add_cell(2, 0..=99);
+        search
+    }
+
+    #[test]
+    fn finds_noun_above_verb() {
+        //tuple_combinations only visits noun < verb and misses 50, 3
+        let mut search = day2(100 * 50 + 3 + 5);
+        assert!(search.check(&[50, 3]));
+        for threads in &[1, 4] {
+            search.set_threads(*threads);
+            assert_eq!(search.exhaustive().unwrap(), Some(vec![50, 3]));
+        }
+        assert_eq!(search.symbolic().unwrap(), Some(vec![50, 3]));
+        assert_eq!(day2(20000).exhaustive().unwrap(), None);
+        assert_eq!(day2(20000).symbolic().unwrap(), None);
+    }
+
+    #[test]
+    fn outputs_and_errors() {
+        //Outputs [1] * [2] and halts with 0
+        let program = [1102, 0, 0, 11, 4, 11, 1101, 0, 0, 0, 99, 0];
+        let mut search = Search::with_predicate(&program, |_, output: &[i64]| output == [42]);
+        search.add_cell(1, 0..=10);
+        search.add_cell(2, 0..=10);
+        assert_eq!(search.exhaustive().unwrap(), Some(vec![6, 7]));
+        assert!(matches!(search.symbolic(), Err(SearchError::Predicate)));
+
+        search.add_cell(3, i64::MIN..=i64::MAX);
+        assert!(matches!(search.exhaustive(), Err(SearchError::TooLarge)));
+    }
+
+    #[test]
+    fn derives_expression() {
+        let expression = day2(0).expression().unwrap();
+        assert_eq!(expression.to_string(), "5 + 100*[1] + [2]");
+        let values = [(1, 4), (2, 7)].iter().copied().collect();
+        assert_eq!(expression.evaluate(&values), Some(412));
+        let square = Polynomial::cell(1)
+            .checked_mul(&Polynomial::cell(1))
+            .unwrap();
+        let square = square.checked_add(&Polynomial::constant(-2)).unwrap();
+        assert_eq!(square.to_string(), "-2 + [1]*[1]");
+
+        //A jump on a patched cell can not be followed
+        let mut search = Search::new(&[1005, 1, 4, 99, 99], 0);
+        search.add_cell(1, 0..=1);
+        assert!(matches!(
+            search.expression(),
+            Err(SearchError::Depends { pc: 0 })
+        ));
+    }
+}
@@ -0,0 +1,523 @@
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::mpsc::{self, Sender};
+
+use crate::computer::{Computer, Instruction};
+use crate::error::IntcodeError;
+use crate::word::Word;
+
+const MAGIC: &[u8; 4] = b"ICTR";
+const VERSION: u8 = 1;
+
+//One executed instruction: where it was, what it did and which memory it touched.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TraceRecord<W = i64> {
+    pub pc: usize,
+    pub instruction: Instruction,
+    pub operands: Vec<W>,
+    pub writes: Vec<(usize, W)>,
+}
+
+pub trait Tracer<W = i64> {
+    fn record(&mut self, record: &TraceRecord<W>) -> io::Result<()>;
+
+    //Called once the run is over, halted or not. Buffered tracers have to
+    //write out everything here.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Word> Tracer<W> for Sender<TraceRecord<W>> {
+    fn record(&mut self, record: &TraceRecord<W>) -> io::Result<()> {
+        self.send(record.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "trace receiver is gone"))
+    }
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn join<T: fmt::Display>(values: impl Iterator<Item = T>) -> String {
+    values
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+//One JSON object per line, meant to be read by humans and scripts.
+pub struct JsonTracer<W> {
+    writer: W,
+}
+
+impl<W: Write> JsonTracer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonTracer { writer }
+    }
+}
+
+impl<W: Write, V: Word> Tracer<V> for JsonTracer<W> {
+    fn record(&mut self, record: &TraceRecord<V>) -> io::Result<()> {
+        writeln!(
+            self.writer,
+            r#"{{"pc":{},"op":"{}","addresses":[{}],"operands":[{}],"writes":[{}]}}"#,
+            record.pc,
+            record.instruction.mnemonic(),
+            join(record.instruction.addresses().iter()),
+            join(record.operands.iter()),
+            join(
+                record
+                    .writes
+                    .iter()
+                    .map(|(address, value)| format!("[{},{}]", address, value))
+            ),
+        )
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+//Compact little-endian format: a header, then per record the pc, the opcode,
+//the parameter addresses and length prefixed operand and write lists.
+pub struct BinaryTracer<W> {
+    writer: W,
+    header_written: bool,
+}
+
+impl<W: Write> BinaryTracer<W> {
+    pub fn new(writer: W) -> Self {
+        BinaryTracer {
+            writer,
+            header_written: false,
+        }
+    }
+}
+
+impl<W: Write> BinaryTracer<W> {
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_written {
+            self.writer.write_all(MAGIC)?;
+            self.writer.write_all(&[VERSION])?;
+            self.header_written = true;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Tracer for BinaryTracer<W> {
+    fn record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        self.write_header()?;
+        let w = &mut self.writer;
+        w.write_all(&(record.pc as u64).to_le_bytes())?;
+        w.write_all(&[record.instruction.opcode() as u8])?;
+        for address in record.instruction.addresses() {
+            w.write_all(&(address as u64).to_le_bytes())?;
+        }
+        w.write_all(&[record.operands.len() as u8])?;
+        for operand in &record.operands {
+            w.write_all(&operand.to_le_bytes())?;
+        }
+        w.write_all(&[record.writes.len() as u8])?;
+        for (address, value) in &record.writes {
+            w.write_all(&(*address as u64).to_le_bytes())?;
+            w.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    //A trace of a program that faults on its first instruction is still a
+    //valid, empty trace.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.writer.flush()
+    }
+}
+
+//Reads traces written by either tracer, the format is detected from the header.
+pub struct TraceReader<R> {
+    reader: R,
+    binary: bool,
+}
+
+impl<R: BufRead> TraceReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let binary = reader.fill_buf()?.starts_with(MAGIC);
+        if binary {
+            let mut header = [0; 5];
+            reader.read_exact(&mut header)?;
+            if header[4] != VERSION {
+                return Err(invalid("unsupported trace version"));
+            }
+        }
+        Ok(TraceReader { reader, binary })
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        let mut buf = [0; 1];
+        self.reader.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        let mut buf = [0; 8];
+        self.reader.read_exact(&mut buf)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    fn binary_record(&mut self) -> io::Result<Option<TraceRecord>> {
+        if self.reader.fill_buf()?.is_empty() {
+            return Ok(None);
+        }
+        let pc = self.u64()? as usize;
+        let opcode = self.u8()? as i64;
+        let count = crate::computer::opcode_info(opcode)
+            .ok_or_else(|| invalid("unknown opcode in trace"))?
+            .1;
+        let addresses = (0..count)
+            .map(|_| self.u64().map(|address| address as usize))
+            .collect::<io::Result<Vec<_>>>()?;
+        let operand_count = self.u8()?;
+        let operands = (0..operand_count)
+            .map(|_| self.u64().map(|value| value as i64))
+            .collect::<io::Result<Vec<_>>>()?;
+        let write_count = self.u8()?;
+        let writes = (0..write_count)
+            .map(|_| Ok((self.u64()? as usize, self.u64()? as i64)))
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Some(TraceRecord {
+            pc,
+            instruction: Instruction::from_parts(opcode, &addresses).unwrap(),
+            operands,
+            writes,
+        }))
+    }
+
+    fn json_record(&mut self) -> io::Result<Option<TraceRecord>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+            if !line.trim().is_empty() {
+                return parse_json_record(&line).map(Some);
+            }
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let record = if self.binary {
+            self.binary_record()
+        } else {
+            self.json_record()
+        };
+        record.transpose()
+    }
+}
+
+//Just enough JSON for the lines JsonTracer writes.
+enum Json {
+    Number(i64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+fn parse_json(text: &mut std::iter::Peekable<std::str::Chars>) -> io::Result<Json> {
+    while text.peek().is_some_and(|c| c.is_whitespace()) {
+        text.next();
+    }
+    match text.next() {
+        Some('"') => {
+            let mut string = String::new();
+            loop {
+                match text.next() {
+                    Some('"') => return Ok(Json::Str(string)),
+                    Some(c) => string.push(c),
+                    None => return Err(invalid("unterminated string")),
+                }
+            }
+        }
+        Some(open @ '[') | Some(open @ '{') => {
+            let close = if open == '[' { ']' } else { '}' };
+            let mut items = Vec::new();
+            let mut fields = Vec::new();
+            loop {
+                while text.peek().is_some_and(|c| c.is_whitespace() || *c == ',') {
+                    text.next();
+                }
+                if text.peek() == Some(&close) {
+                    text.next();
+                    break;
+                }
+                if open == '[' {
+                    items.push(parse_json(text)?);
+                    continue;
+                }
+                let key = match parse_json(text)? {
+                    Json::Str(key) => key,
+                    _ => return Err(invalid("expected a key")),
+                };
+                while text.peek().is_some_and(|c| c.is_whitespace() || *c == ':') {
+                    text.next();
+                }
+                fields.push((key, parse_json(text)?));
+            }
+            Ok(if open == '[' {
+                Json::Array(items)
+            } else {
+                Json::Object(fields)
+            })
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = c.to_string();
+            while let Some(&c) = text.peek().filter(|c| c.is_ascii_digit()) {
+                number.push(c);
+                text.next();
+            }
+            number
+                .parse()
+                .map(Json::Number)
+                .map_err(|_| invalid("bad number"))
+        }
+        _ => Err(invalid("unexpected character")),
+    }
+}
+
+fn parse_json_record(line: &str) -> io::Result<TraceRecord> {
+    let fields = match parse_json(&mut line.chars().peekable())? {
+        Json::Object(fields) => fields,
+        _ => return Err(invalid("trace line is no object")),
+    };
+    let field = |name: &str| {
+        fields
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| invalid("missing field in trace line"))
+    };
+    let numbers = |value: &Json| match value {
+        Json::Array(items) => items
+            .iter()
+            .map(|item| match item {
+                Json::Number(number) => Ok(*number),
+                _ => Err(invalid("expected a number")),
+            })
+            .collect::<io::Result<Vec<_>>>(),
+        _ => Err(invalid("expected an array")),
+    };
+
+    let pc = match field("pc")? {
+        Json::Number(pc) => *pc as usize,
+        _ => return Err(invalid("pc is no number")),
+    };
+    let opcode = match field("op")? {
+        Json::Str(mnemonic) => crate::asm::find_opcode(mnemonic),
+        _ => None,
+    }
+    .ok_or_else(|| invalid("unknown mnemonic in trace"))?;
+    let addresses = numbers(field("addresses")?)?
+        .into_iter()
+        .map(|address| address as usize)
+        .collect::<Vec<_>>();
+    let instruction = Instruction::from_parts(opcode, &addresses)
+        .ok_or_else(|| invalid("wrong number of addresses"))?;
+    let operands = numbers(field("operands")?)?;
+    let writes = match field("writes")? {
+        Json::Array(items) => items
+            .iter()
+            .map(|item| match numbers(item)?.as_slice() {
+                [address, value] => Ok((*address as usize, *value)),
+                _ => Err(invalid("a write is an address and a value")),
+            })
+            .collect::<io::Result<Vec<_>>>()?,
+        _ => return Err(invalid("expected an array")),
+    };
+    Ok(TraceRecord {
+        pc,
+        instruction,
+        operands,
+        writes,
+    })
+}
+
+#[derive(Debug)]
+pub enum ReplayError {
+    Io(io::Error),
+    Fault(IntcodeError),
+    //The first step where the interpreter did something else than the trace says
+    Diverged {
+        step: usize,
+        expected: Box<TraceRecord>,
+        actual: Option<Box<TraceRecord>>,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Io(err) => write!(f, "could not read trace: {}", err),
+            ReplayError::Fault(err) => write!(f, "{}", err),
+            ReplayError::Diverged {
+                step,
+                expected,
+                actual,
+            } => {
+                writeln!(f, "diverged at step {}", step)?;
+                writeln!(f, "expected: {:?}", expected)?;
+                write!(f, "actual:   {:?}", actual)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+//Runs program against a recorded trace and checks every instruction. Inputs
+//are taken from the writes of the recorded input instructions. Returns the
+//number of replayed steps.
+pub fn replay(
+    program: &[i64],
+    trace: impl Iterator<Item = io::Result<TraceRecord>>,
+) -> Result<usize, ReplayError> {
+    let (sender, receiver) = mpsc::channel();
+    let mut computer = Computer::new(program);
+    computer.set_tracer(Box::new(sender));
+    let mut steps = 0;
+    for expected in trace {
+        let expected = expected.map_err(ReplayError::Io)?;
+        if let Instruction::Input { .. } = expected.instruction {
+            if let Some(&(_, value)) = expected.writes.first() {
+                computer.push_input(value);
+            }
+        }
+        computer.step().map_err(ReplayError::Fault)?;
+        let actual = receiver.try_recv().ok();
+        if actual.as_ref() != Some(&expected) {
+            return Err(ReplayError::Diverged {
+                step: steps,
+                expected: Box::new(expected),
+                actual: actual.map(Box::new),
+            });
+        }
+        steps += 1;
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
+
+    //Outputs the input plus 5
+    const PROGRAM: [i64; 9] = [3, 9, 1001, 9, 5, 9, 4, 9, 99];
+
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn record(tracer: Box<dyn Tracer + Send>) -> Computer {
+        let mut computer = Computer::new(&PROGRAM);
+        computer.set_tracer(tracer);
+        computer
+            .run(&mut VecDeque::from(vec![3]), &mut Vec::new())
+            .unwrap();
+        computer
+    }
+
+    #[test]
+    fn records_reads_and_writes() {
+        let (sender, receiver) = mpsc::channel();
+        record(Box::new(sender));
+        let records = receiver.try_iter().collect::<Vec<_>>();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].writes, vec![(9, 3)]);
+        assert_eq!(records[1].pc, 2);
+        assert_eq!(records[1].instruction.addresses(), vec![9, 4, 9]);
+        assert_eq!(records[1].operands, vec![3, 5]);
+        assert_eq!(records[1].writes, vec![(9, 8)]);
+        assert_eq!(records[2].operands, vec![8]);
+        assert_eq!(records[3].instruction, Instruction::Halt);
+    }
+
+    #[test]
+    fn json_roundtrip() {
+        let (sender, receiver) = mpsc::channel();
+        record(Box::new(sender));
+        let mut text = Vec::new();
+        let mut tracer = JsonTracer::new(&mut text);
+        let records = receiver.try_iter().collect::<Vec<_>>();
+        for record in &records {
+            tracer.record(record).unwrap();
+        }
+        let line = String::from_utf8(text.clone()).unwrap();
+        assert!(line
+            .starts_with(r#"{"pc":0,"op":"IN","addresses":[9],"operands":[],"writes":[[9,3]]}"#));
+        let read = TraceReader::new(&text[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(read, records);
+        assert_eq!(replay(&PROGRAM, read.into_iter().map(Ok)).unwrap(), 4);
+    }
+
+    #[test]
+    fn faulting_runs_are_flushed() {
+        //Stores 3 and 7, then hits opcode 42
+        let program = [1101, 1, 2, 20, 1101, 3, 4, 21, 1, 20, 21, 22, 42];
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        let mut computer = Computer::new(&program);
+        computer.set_tracer(Box::new(BinaryTracer::new(io::BufWriter::new(Shared(
+            buf.clone(),
+        )))));
+        assert!(computer.run(&mut VecDeque::new(), &mut Vec::new()).is_err());
+        assert!(buf.lock().unwrap().is_empty());
+        computer.take_tracer().unwrap().flush().unwrap();
+
+        let bytes = buf.lock().unwrap().clone();
+        let records = TraceReader::new(&bytes[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[2].writes, vec![(22, 10)]);
+    }
+
+    #[test]
+    fn binary_replay_detects_divergence() {
+        let buf = Arc::new(Mutex::new(Vec::new()));
+        record(Box::new(BinaryTracer::new(Shared(buf.clone()))));
+        let bytes = buf.lock().unwrap().clone();
+        let trace = || TraceReader::new(&bytes[..]).unwrap();
+        assert_eq!(replay(&PROGRAM, trace()).unwrap(), 4);
+
+        //A changed program adds 6 instead of 5
+        let mut modified = PROGRAM;
+        modified[4] = 6;
+        match replay(&modified, trace()) {
+            Err(ReplayError::Diverged { step, actual, .. }) => {
+                assert_eq!(step, 1);
+                assert_eq!(actual.unwrap().writes, vec![(9, 9)]);
+            }
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::num::ParseIntError;
+
+use crate::error::ErrorKind;
+
+//Value type of intcode memory. Narrow words save memory, wide words keep huge
+//intermediate results exact.
+pub trait Word: Copy + Ord + Hash + Default + Debug + Display + Send + Sync + 'static {
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn parse(text: &str) -> Result<Self, ParseIntError>;
+    fn from_i64(value: i64) -> Option<Self>;
+    fn from_i128(value: i128) -> Option<Self>;
+    //Every word type fits, so errors and instruction decoding use i128.
+    fn to_i128(self) -> i128;
+
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn saturating_add(self, other: Self) -> Self;
+    fn saturating_mul(self, other: Self) -> Self;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+
+    //The only place where words turn into addresses.
+    fn to_address(self) -> Result<usize, ErrorKind> {
+        let value = self.to_i128();
+        if value < 0 {
+            return Err(ErrorKind::NegativeAddress(value));
+        }
+        usize::try_from(value).map_err(|_| ErrorKind::AddressOutOfBounds)
+    }
+}
+
+macro_rules! impl_word {
+    ($($word:ty),*) => {$(
+        impl Word for $word {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+
+            fn parse(text: &str) -> Result<Self, ParseIntError> {
+                text.parse()
+            }
+
+            fn from_i64(value: i64) -> Option<Self> {
+                <$word>::try_from(value).ok()
+            }
+
+            fn from_i128(value: i128) -> Option<Self> {
+                <$word>::try_from(value).ok()
+            }
+
+            fn to_i128(self) -> i128 {
+                self as i128
+            }
+
+            fn checked_add(self, other: Self) -> Option<Self> {
+                <$word>::checked_add(self, other)
+            }
+
+            fn checked_mul(self, other: Self) -> Option<Self> {
+                <$word>::checked_mul(self, other)
+            }
+
+            fn saturating_add(self, other: Self) -> Self {
+                <$word>::saturating_add(self, other)
+            }
+
+            fn saturating_mul(self, other: Self) -> Self {
+                <$word>::saturating_mul(self, other)
+            }
+
+            fn wrapping_add(self, other: Self) -> Self {
+                <$word>::wrapping_add(self, other)
+            }
+
+            fn wrapping_mul(self, other: Self) -> Self {
+                <$word>::wrapping_mul(self, other)
+            }
+        }
+    )*};
+}
+
+impl_word!(i32, i64, i128);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn addresses_are_checked() {
+        assert_eq!(7i32.to_address().unwrap(), 7);
+        assert!(matches!(
+            (-3i64).to_address(),
+            Err(ErrorKind::NegativeAddress(-3))
+        ));
+        assert!(matches!(
+            i128::MAX.to_address(),
+            Err(ErrorKind::AddressOutOfBounds)
+        ));
+    }
+}